@@ -0,0 +1,313 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::{report, solver, InputResolver, PartResult};
+
+pub const ANSWERS_FILE: &str = "answers.toml";
+
+/// Environment variable which makes [`assert_answer`] record answers it has no expectation for.
+pub const RECORD_VAR: &str = "AOC_RECORD_ANSWERS";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+struct Expected {
+    part1: Option<String>,
+    part2: Option<String>,
+}
+
+/// Known-good answers, keyed by day and then input file, as stored in `res/answers.toml`:
+///
+/// ```toml
+/// [day8."day8.txt"]
+/// part1 = "1087"
+/// part2 = "780"
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Answers {
+    entries: BTreeMap<(u32, String), Expected>,
+}
+
+impl Answers {
+    pub fn parse(text: &str) -> Result<Answers> {
+        let raw: BTreeMap<String, BTreeMap<String, Expected>> = toml::from_str(text)?;
+        let mut entries = BTreeMap::new();
+        for (day_key, inputs) in raw {
+            let day = day_key
+                .strip_prefix("day")
+                .and_then(|d| d.parse().ok())
+                .with_context(|| format!("Invalid day key {}", day_key))?;
+            for (input, expected) in inputs {
+                entries.insert((day, input), expected);
+            }
+        }
+        Ok(Answers { entries })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Answers> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        Answers::parse(&text).with_context(|| format!("Invalid answers file {}", path.display()))
+    }
+
+    /// Where `answers.toml` lives by default; the file itself may not exist yet.
+    pub fn default_path(resolver: &InputResolver) -> PathBuf {
+        resolver.resolve(ANSWERS_FILE).unwrap_or_else(|_| {
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("res")
+                .join(ANSWERS_FILE)
+        })
+    }
+
+    /// Loads `path`, treating a missing file as an empty store.
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Result<Answers> {
+        if path.as_ref().exists() {
+            Answers::load(path)
+        } else {
+            Ok(Answers::default())
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_string())
+            .with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    pub fn get(&self, day: u32, part: u32, input: &str) -> Option<&str> {
+        let expected = self.entries.get(&(day, input.to_owned()))?;
+        match part {
+            1 => expected.part1.as_deref(),
+            2 => expected.part2.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&mut self, day: u32, part: u32, input: &str, answer: &str) -> Result<()> {
+        let expected = self.entries.entry((day, input.to_owned())).or_default();
+        match part {
+            1 => expected.part1 = Some(answer.to_owned()),
+            2 => expected.part2 = Some(answer.to_owned()),
+            _ => bail!("No part {}", part),
+        }
+        Ok(())
+    }
+
+    /// Runs every registered day through [`Answers::verify_day`].
+    pub fn verify(&self, resolver: &InputResolver) -> Vec<Verification> {
+        solver::registry()
+            .iter()
+            .flat_map(|entry| self.verify_day(resolver, entry.day))
+            .collect()
+    }
+
+    /// Checks `day{N}.txt` and every other input with a stored answer for `day` against its solver.
+    pub fn verify_day(&self, resolver: &InputResolver, day: u32) -> Vec<Verification> {
+        let mut inputs: BTreeSet<String> = self
+            .entries
+            .keys()
+            .filter(|(d, _)| *d == day)
+            .map(|(_, input)| input.clone())
+            .collect();
+        inputs.insert(format!("day{}.txt", day));
+
+        let mut result = vec![];
+        for input in inputs {
+            let results = match resolver.read_to_string(&input) {
+                Ok(text) => report::run(day, &[1, 2], &text),
+                Err(e) => vec![
+                    PartResult::failed(day, 1, &e),
+                    PartResult::failed(day, 2, &e),
+                ],
+            };
+            for part_result in results {
+                let status = match self.get(day, part_result.part, &input) {
+                    None => Status::Missing,
+                    Some(expected) if part_result.answer.as_deref() == Some(expected) => {
+                        Status::Pass
+                    }
+                    Some(expected) => Status::Fail {
+                        expected: expected.to_owned(),
+                    },
+                };
+                result.push(Verification {
+                    input: input.clone(),
+                    result: part_result,
+                    status,
+                });
+            }
+        }
+        result
+    }
+
+    /// Stores the answer of every `Missing` verification which produced one. Returns how many were added.
+    pub fn record(&mut self, verifications: &[Verification]) -> Result<usize> {
+        let mut count = 0;
+        for v in verifications {
+            if let (Status::Missing, Some(answer)) = (&v.status, &v.result.answer) {
+                self.insert(v.result.day, v.result.part, &v.input, answer)?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl Display for Answers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "# Expected answers, keyed by day and input file. Add new ones with `aoc verify --record`."
+        )?;
+        for ((day, input), expected) in &self.entries {
+            writeln!(f)?;
+            writeln!(f, "[day{}.{}]", day, toml::Value::String(input.clone()))?;
+            if let Some(answer) = &expected.part1 {
+                writeln!(f, "part1 = {}", toml::Value::String(answer.clone()))?;
+            }
+            if let Some(answer) = &expected.part2 {
+                writeln!(f, "part2 = {}", toml::Value::String(answer.clone()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Fail { expected: String },
+    Missing,
+}
+
+#[derive(Debug, Clone)]
+pub struct Verification {
+    pub input: String,
+    pub result: PartResult,
+    pub status: Status,
+}
+
+impl Display for Verification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let actual = match (&self.result.answer, &self.result.error) {
+            (Some(answer), _) => answer.clone(),
+            (None, Some(error)) => format!("error: {}", error),
+            (None, None) => "nothing".to_owned(),
+        };
+        let label = format!(
+            "day {} part {} ({})",
+            self.result.day, self.result.part, self.input
+        );
+        match &self.status {
+            Status::Pass => write!(f, "PASS    {}: {}", label, actual),
+            Status::Fail { expected } => {
+                write!(
+                    f,
+                    "FAIL    {}: expected {}, got {}",
+                    label, expected, actual
+                )
+            }
+            Status::Missing => write!(f, "MISSING {}: got {}", label, actual),
+        }
+    }
+}
+
+/// Test helper: asserts `answer` matches the stored answer for this day/part/input.
+/// Without a stored answer it only warns, unless `AOC_RECORD_ANSWERS` is set, in which case it is saved.
+#[cfg(test)]
+pub(crate) fn assert_answer<T: Display>(day: u32, part: u32, input: &str, answer: T) {
+    use lazy_static::lazy_static;
+    use std::sync::Mutex;
+
+    lazy_static! {
+        static ref PATH: PathBuf = Answers::default_path(&InputResolver::new());
+        static ref ANSWERS: Mutex<Answers> = Mutex::new(Answers::load_or_default(&*PATH).unwrap());
+    }
+    let answer = answer.to_string();
+    let mut answers = ANSWERS.lock().unwrap();
+    match answers.get(day, part, input) {
+        Some(expected) => assert_eq!(
+            expected, answer,
+            "Day {} part {} ({}) changed",
+            day, part, input
+        ),
+        None if std::env::var_os(RECORD_VAR).is_some() => {
+            answers.insert(day, part, input, &answer).unwrap();
+            answers.save(&*PATH).unwrap();
+        }
+        None => eprintln!(
+            "No stored answer for day {} part {} ({}): {}",
+            day, part, input, answer
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let mut answers = Answers::default();
+        answers.insert(8, 1, "day8.txt", "1087")?;
+        answers.insert(8, 2, "day8.txt", "780")?;
+        answers.insert(10, 1, "day10_smoke1.txt", "35")?;
+        answers.insert(21, 2, "day21.txt", "a,b\"c")?;
+
+        let text = answers.to_string();
+        assert!(text.contains("[day8.\"day8.txt\"]\npart1 = \"1087\"\npart2 = \"780\"\n"));
+        let parsed = Answers::parse(&text)?;
+        assert_eq!(answers, parsed);
+        assert_eq!(Some("a,b\"c"), parsed.get(21, 2, "day21.txt"));
+        assert_eq!(None, parsed.get(10, 2, "day10_smoke1.txt"));
+        assert_eq!(None, parsed.get(9, 1, "day9.txt"));
+        Ok(())
+    }
+
+    #[test]
+    fn bad_day_key() {
+        assert!(Answers::parse("[eight.\"day8.txt\"]\npart1 = \"1\"\n").is_err());
+    }
+
+    #[test]
+    fn verify_statuses() -> Result<()> {
+        let mut answers =
+            Answers::parse("[day1.\"day1_smoke.txt\"]\npart1 = \"514579\"\npart2 = \"1\"\n")?;
+        let root = std::env::temp_dir().join(format!("aoc_answers_{}", std::process::id()));
+        fs::create_dir_all(&root)?;
+        fs::write(
+            root.join("day1_smoke.txt"),
+            "1721\n979\n366\n299\n675\n1456\n",
+        )?;
+        let verifications = answers.verify_day(&InputResolver::with_root(&root), 1);
+        fs::remove_dir_all(&root)?;
+
+        let smoke: Vec<_> = verifications
+            .iter()
+            .filter(|v| v.input == "day1_smoke.txt")
+            .collect();
+        assert_eq!(Status::Pass, smoke[0].status);
+        assert_eq!(
+            Status::Fail {
+                expected: "1".to_owned()
+            },
+            smoke[1].status
+        );
+        assert!(verifications
+            .iter()
+            .filter(|v| v.input == "day1.txt")
+            .all(|v| v.status == Status::Missing));
+
+        assert_eq!(2, answers.record(&verifications)?);
+        assert_eq!(Some("744475"), answers.get(1, 1, "day1.txt"));
+        // Failures are never overwritten by recording.
+        assert_eq!(Some("1"), answers.get(1, 2, "day1_smoke.txt"));
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::solver;
+
+/// Min/median/max over every sample of one phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timings {
+    #[serde(with = "nanos")]
+    pub min: Duration,
+    #[serde(with = "nanos")]
+    pub median: Duration,
+    #[serde(with = "nanos")]
+    pub max: Duration,
+}
+
+impl Timings {
+    pub fn from_samples(samples: &[Duration]) -> Result<Timings> {
+        if samples.is_empty() {
+            bail!("No samples");
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        Ok(Timings {
+            min: sorted[0],
+            median: sorted[sorted.len() / 2],
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+mod nanos {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_nanos() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Ok(Duration::from_nanos(u64::deserialize(deserializer)?))
+    }
+}
+
+/// Timings for one day. A part which fails is left out rather than failing the whole day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayBench {
+    pub day: u32,
+    pub iterations: usize,
+    pub parse: Timings,
+    pub part1: Option<Timings>,
+    pub part2: Option<Timings>,
+}
+
+impl DayBench {
+    pub fn phases(&self) -> Vec<(&'static str, Timings)> {
+        let mut result = vec![("parse", self.parse)];
+        if let Some(t) = self.part1 {
+            result.push(("part1", t));
+        }
+        if let Some(t) = self.part2 {
+            result.push(("part2", t));
+        }
+        result
+    }
+}
+
+/// Parses `input` and solves both parts `iterations` times, timing each phase separately.
+pub fn bench_day(day: u32, input: &str, iterations: usize) -> Result<DayBench> {
+    if iterations == 0 {
+        bail!("Need at least one iteration");
+    }
+    let entry = solver::lookup(day)?;
+    let mut parse = vec![];
+    let mut parts = [Some(vec![]), Some(vec![])];
+
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let puzzle = (entry.parse)(input)?;
+        parse.push(start.elapsed());
+
+        for (idx, samples) in parts.iter_mut().enumerate() {
+            if let Some(s) = samples {
+                let start = Instant::now();
+                let answer = puzzle.solve(idx as u32 + 1);
+                let elapsed = start.elapsed();
+                match answer {
+                    Ok(_) => s.push(elapsed),
+                    Err(_) => *samples = None,
+                }
+            }
+        }
+    }
+
+    let [part1, part2] = parts;
+    Ok(DayBench {
+        day,
+        iterations,
+        parse: Timings::from_samples(&parse)?,
+        part1: part1.map(|s| Timings::from_samples(&s)).transpose()?,
+        part2: part2.map(|s| Timings::from_samples(&s)).transpose()?,
+    })
+}
+
+/// Saved benchmark results, one JSON line per day.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Baseline {
+    days: HashMap<u32, DayBench>,
+}
+
+impl Baseline {
+    pub fn new(results: &[DayBench]) -> Baseline {
+        Baseline {
+            days: results.iter().map(|b| (b.day, b.clone())).collect(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Baseline> {
+        let mut days = HashMap::new();
+        for (idx, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let bench: DayBench = serde_json::from_str(line)
+                .with_context(|| format!("Invalid baseline on line {}", idx + 1))?;
+            days.insert(bench.day, bench);
+        }
+        Ok(Baseline { days })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Baseline> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        Baseline::parse(&text)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        let mut days: Vec<_> = self.days.values().collect();
+        days.sort_by_key(|b| b.day);
+        let mut result = String::new();
+        for bench in days {
+            result.push_str(&serde_json::to_string(bench)?);
+            result.push('\n');
+        }
+        Ok(result)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_json()?)
+            .with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    pub fn get(&self, day: u32) -> Option<&DayBench> {
+        self.days.get(&day)
+    }
+
+    /// Median of each phase of `current` against the same phase here, if it was measured.
+    pub fn compare(&self, current: &DayBench) -> Vec<Comparison> {
+        let baseline = self.get(current.day);
+        current
+            .phases()
+            .into_iter()
+            .map(|(phase, timings)| Comparison {
+                day: current.day,
+                phase,
+                timings,
+                baseline: baseline.and_then(|b| {
+                    b.phases()
+                        .into_iter()
+                        .find(|(p, _)| *p == phase)
+                        .map(|(_, t)| t.median)
+                }),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    pub day: u32,
+    pub phase: &'static str,
+    pub timings: Timings,
+    pub baseline: Option<Duration>,
+}
+
+impl Comparison {
+    /// Relative change of the median against the baseline, e.g. -0.5 when twice as fast.
+    pub fn change(&self) -> Option<f64> {
+        let baseline = self.baseline?.as_secs_f64();
+        if baseline == 0.0 {
+            return None;
+        }
+        Some(self.timings.median.as_secs_f64() / baseline - 1.0)
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "day {:>2} {:<5}  min {:>10.3?}  median {:>10.3?}  max {:>10.3?}",
+            self.day, self.phase, self.timings.min, self.timings.median, self.timings.max
+        )?;
+        match (self.baseline, self.change()) {
+            (Some(baseline), Some(change)) => write!(
+                f,
+                "  baseline {:>10.3?} ({:+.1}%)",
+                baseline,
+                change * 100.0
+            ),
+            (Some(baseline), None) => write!(f, "  baseline {:>10.3?}", baseline),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY1_SMOKE: &str = "1721\n979\n366\n299\n675\n1456\n";
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn timings() -> Result<()> {
+        let t = Timings::from_samples(&[ms(5), ms(1), ms(9), ms(3), ms(7)])?;
+        assert_eq!(ms(1), t.min);
+        assert_eq!(ms(5), t.median);
+        assert_eq!(ms(9), t.max);
+        assert!(Timings::from_samples(&[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn bench_smoke() -> Result<()> {
+        let bench = bench_day(1, DAY1_SMOKE, 3)?;
+        assert_eq!(3, bench.iterations);
+        assert!(bench.part1.is_some());
+        assert!(bench.part2.is_some());
+        assert!(bench.parse.min <= bench.parse.median && bench.parse.median <= bench.parse.max);
+
+        assert!(bench_day(1, DAY1_SMOKE, 0).is_err());
+        assert!(bench_day(1, "not a number", 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn failing_part_is_skipped() -> Result<()> {
+        let bench = bench_day(25, "5764801\n17807724\n", 1)?;
+        assert!(bench.part1.is_some());
+        assert_eq!(None, bench.part2);
+        assert_eq!(2, bench.phases().len());
+        Ok(())
+    }
+
+    #[test]
+    fn baseline_compare() -> Result<()> {
+        let old = DayBench {
+            day: 1,
+            iterations: 3,
+            parse: Timings::from_samples(&[ms(10)])?,
+            part1: Some(Timings::from_samples(&[ms(20)])?),
+            part2: None,
+        };
+        let baseline = Baseline::parse(&Baseline::new(std::slice::from_ref(&old)).to_json()?)?;
+        assert_eq!(Some(&old), baseline.get(1));
+
+        let new = DayBench {
+            parse: Timings::from_samples(&[ms(5)])?,
+            part2: Some(Timings::from_samples(&[ms(1)])?),
+            ..old
+        };
+        let comparisons = baseline.compare(&new);
+        assert_eq!(3, comparisons.len());
+        assert_eq!(Some(-0.5), comparisons[0].change());
+        assert_eq!(Some(0.0), comparisons[1].change());
+        assert_eq!(None, comparisons[2].baseline);
+        assert!(comparisons[0].to_string().contains("(-50.0%)"));
+        Ok(())
+    }
+}
@@ -0,0 +1,341 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, BufWriter, Write},
+    process,
+};
+
+use anyhow::{bail, Context, Result};
+use aoc_2020::{
+    answers::{Answers, Status},
+    bench::{self, Baseline},
+    debugger::{self, Debugger},
+    handheld::Program,
+    report, solver, InputResolver,
+};
+
+const USAGE: &str = "Usage:
+    aoc run <day> [<part>] [--input <path>|-] [--json]
+    aoc batch [--input-dir <dir>] [--output <path>]
+    aoc verify [<day>] [--input-dir <dir>] [--answers <path>] [--record]
+    aoc bench [<day>...] [-n <iterations>] [--input-dir <dir>] [--baseline <path>] [--save <path>]
+    aoc debug <program> [--break <pc>...] [--watch] [--history <n>]
+    aoc cfg <program> [--output <path>]";
+
+struct RunArgs {
+    day: u32,
+    parts: Vec<u32>,
+    input: String,
+    json: bool,
+}
+
+impl RunArgs {
+    fn parse(args: &[String]) -> Result<RunArgs> {
+        let mut positional = vec![];
+        let mut input = None;
+        let mut json = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--input" | "-i" => {
+                    input = Some(args.next().context("--input needs a value")?.to_owned())
+                }
+                "--json" => json = true,
+                _ => positional.push(arg),
+            }
+        }
+
+        let day: u32 = positional
+            .first()
+            .context("Missing day")?
+            .parse()
+            .context("Day must be a number")?;
+        if !(1..=25).contains(&day) {
+            bail!("Day must be between 1 and 25");
+        }
+        let parts = match positional.get(1) {
+            Some(part) => match part.parse() {
+                Ok(part @ 1..=2) => vec![part],
+                _ => bail!("Part must be 1 or 2"),
+            },
+            None => vec![1, 2],
+        };
+        if positional.len() > 2 {
+            bail!("Unexpected argument {}", positional[2]);
+        }
+
+        Ok(RunArgs {
+            day,
+            parts,
+            input: input.unwrap_or_else(|| format!("day{}.txt", day)),
+            json,
+        })
+    }
+}
+
+fn run(args: &[String]) -> Result<()> {
+    let args = RunArgs::parse(args)?;
+    if args.json {
+        let input = aoc_2020::read_input(&args.input)?;
+        let results = report::run(args.day, &args.parts, &input);
+        for result in &results {
+            println!("{}", result.to_json()?);
+        }
+        let failed = results.iter().filter(|r| r.error.is_some()).count();
+        if failed > 0 {
+            bail!("{} of {} parts failed", failed, results.len());
+        }
+        return Ok(());
+    }
+
+    for part in &args.parts {
+        let answer = aoc_2020::solve(args.day, *part, &args.input)
+            .with_context(|| format!("Day {} part {} failed", args.day, part))?;
+        println!("{}", answer);
+    }
+    Ok(())
+}
+
+fn batch(args: &[String]) -> Result<()> {
+    let mut resolver = InputResolver::new();
+    let mut output: Box<dyn Write> = Box::new(io::stdout());
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input-dir" => {
+                resolver =
+                    InputResolver::with_root(args.next().context("--input-dir needs a value")?)
+            }
+            "--output" | "-o" => {
+                let path = args.next().context("--output needs a value")?;
+                let file =
+                    File::create(path).with_context(|| format!("Could not create {}", path))?;
+                output = Box::new(BufWriter::new(file));
+            }
+            _ => bail!("Unexpected argument {}", arg),
+        }
+    }
+
+    let results = report::run_all(&resolver);
+    for result in &results {
+        writeln!(output, "{}", result.to_json()?)?;
+    }
+    output.flush()?;
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    eprintln!("{} answers, {} errors", results.len() - failed, failed);
+    Ok(())
+}
+
+fn verify(args: &[String]) -> Result<()> {
+    let mut resolver = InputResolver::new();
+    let mut answers_path = None;
+    let mut record = false;
+    let mut day = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input-dir" => {
+                resolver =
+                    InputResolver::with_root(args.next().context("--input-dir needs a value")?)
+            }
+            "--answers" => {
+                answers_path = Some(args.next().context("--answers needs a value")?.into())
+            }
+            "--record" => record = true,
+            _ if day.is_none() => day = Some(arg.parse::<u32>().context("Day must be a number")?),
+            _ => bail!("Unexpected argument {}", arg),
+        }
+    }
+
+    let answers_path = answers_path.unwrap_or_else(|| Answers::default_path(&resolver));
+    let mut answers = Answers::load_or_default(&answers_path)?;
+    let verifications = match day {
+        Some(day) => answers.verify_day(&resolver, day),
+        None => answers.verify(&resolver),
+    };
+    for v in &verifications {
+        println!("{}", v);
+    }
+
+    let count =
+        |status: fn(&Status) -> bool| verifications.iter().filter(|v| status(&v.status)).count();
+    let passed = count(|s| *s == Status::Pass);
+    let failed = count(|s| matches!(s, Status::Fail { .. }));
+    let missing = count(|s| *s == Status::Missing);
+    println!("{} passed, {} failed, {} missing", passed, failed, missing);
+
+    if record {
+        let recorded = answers.record(&verifications)?;
+        if recorded > 0 {
+            answers.save(&answers_path)?;
+        }
+        println!(
+            "Recorded {} answers in {}",
+            recorded,
+            answers_path.display()
+        );
+    }
+    if failed > 0 {
+        bail!("{} answers did not match", failed);
+    }
+    Ok(())
+}
+
+fn bench(args: &[String]) -> Result<()> {
+    let mut resolver = InputResolver::new();
+    let mut days = vec![];
+    let mut iterations = 10;
+    let mut baseline = Baseline::default();
+    let mut save = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input-dir" => {
+                resolver =
+                    InputResolver::with_root(args.next().context("--input-dir needs a value")?)
+            }
+            "-n" | "--iterations" => {
+                iterations = args
+                    .next()
+                    .context("--iterations needs a value")?
+                    .parse()
+                    .context("Iterations must be a number")?
+            }
+            "--baseline" => {
+                baseline = Baseline::load(args.next().context("--baseline needs a value")?)?
+            }
+            "--save" => save = Some(args.next().context("--save needs a value")?),
+            _ => days.push(arg.parse::<u32>().context("Day must be a number")?),
+        }
+    }
+    if days.is_empty() {
+        days = solver::registry().iter().map(|e| e.day).collect();
+    }
+
+    let mut results = vec![];
+    let mut failed = 0;
+    for day in days {
+        let bench = resolver
+            .read_to_string(&format!("day{}.txt", day))
+            .and_then(|input| bench::bench_day(day, &input, iterations));
+        match bench {
+            Ok(bench) => {
+                for comparison in baseline.compare(&bench) {
+                    println!("{}", comparison);
+                }
+                results.push(bench);
+            }
+            Err(e) => {
+                eprintln!("day {:>2} failed: {:#}", day, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if let Some(path) = save {
+        Baseline::new(&results).save(path)?;
+    }
+    if failed > 0 {
+        bail!("{} days failed", failed);
+    }
+    Ok(())
+}
+
+fn debug(args: &[String]) -> Result<()> {
+    let mut file_name = None;
+    let mut breakpoints = vec![];
+    let mut watch = false;
+    let mut history = 16;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--break" | "-b" => breakpoints.push(
+                args.next()
+                    .context("--break needs a value")?
+                    .parse::<usize>()
+                    .context("Breakpoint must be a number")?,
+            ),
+            "--watch" => watch = true,
+            "--history" => {
+                history = args
+                    .next()
+                    .context("--history needs a value")?
+                    .parse()
+                    .context("History must be a number")?
+            }
+            _ if file_name.is_none() => file_name = Some(arg),
+            _ => bail!("Unexpected argument {}", arg),
+        }
+    }
+    let file_name = file_name.context("Missing program")?;
+    if file_name == aoc_2020::STDIN {
+        bail!("Commands come from stdin, so the program has to be a file");
+    }
+
+    let mut debugger = Debugger::new(Program::load_program(file_name)?).with_history(history);
+    for pc in breakpoints {
+        debugger.add_breakpoint(pc);
+    }
+    debugger.set_watch(watch);
+    debugger::repl(&mut debugger, io::stdin().lock(), io::stdout())
+}
+
+fn cfg(args: &[String]) -> Result<()> {
+    let mut file_name = None;
+    let mut output: Box<dyn Write> = Box::new(io::stdout());
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" | "-o" => {
+                let path = args.next().context("--output needs a value")?;
+                let file =
+                    File::create(path).with_context(|| format!("Could not create {}", path))?;
+                output = Box::new(BufWriter::new(file));
+            }
+            _ if file_name.is_none() => file_name = Some(arg),
+            _ => bail!("Unexpected argument {}", arg),
+        }
+    }
+    let file_name = file_name.context("Missing program")?;
+
+    let program = Program::load_program(file_name)?;
+    let cfg = program.cfg();
+    write!(output, "{}", cfg.to_dot(&program))?;
+    output.flush()?;
+
+    eprintln!(
+        "{} blocks, {} unreachable instructions, {} cycles",
+        cfg.blocks().len(),
+        cfg.unreachable().len(),
+        cfg.cycles().len()
+    );
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(|s| s.as_str()) {
+        Some("run") => run(&args[1..]),
+        Some("batch") => batch(&args[1..]),
+        Some("verify") => verify(&args[1..]),
+        Some("bench") => bench(&args[1..]),
+        Some("debug") => debug(&args[1..]),
+        Some("cfg") => cfg(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {:#}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,357 @@
+//! Control-flow graph of a handheld [`Program`], built without running it.
+
+use std::collections::BTreeSet;
+
+use crate::handheld::{Flow, Program};
+
+/// Where control can go when a block ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Block(usize),
+    /// Just past the last instruction, which terminates the program.
+    Exit,
+    /// A jump to this pc, outside the program. Saturates past the range of an `i64`.
+    OutOfRange(i64),
+    /// A jump by a register, which could go to any instruction.
+    Anywhere,
+}
+
+/// Instructions `start..end`, which always run together from first to last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    pub successors: Vec<Target>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ControlFlowGraph {
+    blocks: Vec<BasicBlock>,
+    /// Index of the block holding each pc.
+    block_of: Vec<usize>,
+}
+
+impl ControlFlowGraph {
+    pub fn new(program: &Program) -> ControlFlowGraph {
+        let len = program.instructions.len();
+        let targets = |pc: usize| -> Vec<Result<usize, Target>> {
+            let jump = |offset: i64| match program.target(pc, offset) {
+                Some(target) => Ok(target),
+                None => Err(Target::OutOfRange((pc as i64).saturating_add(offset))),
+            };
+            match program.instructions[pc].flow() {
+                Flow::Next => vec![Ok(pc + 1)],
+                Flow::Jump(offset) => vec![jump(offset)],
+                Flow::Branch(offset) => vec![Ok(pc + 1), jump(offset)],
+                Flow::Dynamic => vec![Ok(pc + 1), Err(Target::Anywhere)],
+            }
+        };
+
+        // A block starts at 0, at anything jumped to, and after anything which jumps.
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for pc in 0..len {
+            if program.instructions[pc].flow() != Flow::Next {
+                leaders.insert(pc + 1);
+                leaders.extend(targets(pc).into_iter().flatten());
+            }
+        }
+        leaders.retain(|pc| *pc < len);
+
+        let starts: Vec<usize> = leaders.into_iter().collect();
+        let mut block_of = vec![0; len];
+        for (idx, start) in starts.iter().enumerate() {
+            let end = starts.get(idx + 1).copied().unwrap_or(len);
+            for block in &mut block_of[*start..end] {
+                *block = idx;
+            }
+        }
+
+        let blocks = starts
+            .iter()
+            .enumerate()
+            .map(|(idx, start)| {
+                let end = starts.get(idx + 1).copied().unwrap_or(len);
+                let successors = targets(end - 1)
+                    .into_iter()
+                    .map(|target| match target {
+                        Ok(pc) if pc == len => Target::Exit,
+                        Ok(pc) => Target::Block(block_of[pc]),
+                        Err(target) => target,
+                    })
+                    .collect();
+                BasicBlock {
+                    start: *start,
+                    end,
+                    successors,
+                }
+            })
+            .collect();
+
+        ControlFlowGraph { blocks, block_of }
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    pub fn block_at(&self, pc: usize) -> Option<&BasicBlock> {
+        self.block_of.get(pc).map(|idx| &self.blocks[*idx])
+    }
+
+    /// Blocks `idx` can go to next.
+    fn next_blocks(&self, idx: usize) -> Vec<usize> {
+        let mut result = vec![];
+        for target in &self.blocks[idx].successors {
+            match target {
+                Target::Block(next) => result.push(*next),
+                Target::Anywhere => result.extend(0..self.blocks.len()),
+                Target::Exit | Target::OutOfRange(_) => (),
+            }
+        }
+        result
+    }
+
+    fn reachable_blocks(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut todo = vec![];
+        if !self.blocks.is_empty() {
+            todo.push(0);
+        }
+        while let Some(idx) = todo.pop() {
+            if !seen[idx] {
+                seen[idx] = true;
+                todo.extend(self.next_blocks(idx));
+            }
+        }
+        seen
+    }
+
+    /// Every pc which can run, starting from 0.
+    pub fn reachable(&self) -> BTreeSet<usize> {
+        self.reachable_blocks()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, seen)| *seen)
+            .flat_map(|(idx, _)| self.blocks[idx].start..self.blocks[idx].end)
+            .collect()
+    }
+
+    /// Every pc which can never run.
+    pub fn unreachable(&self) -> Vec<usize> {
+        let reachable = self.reachable();
+        (0..self.block_of.len())
+            .filter(|pc| !reachable.contains(pc))
+            .collect()
+    }
+
+    /// Groups of blocks which can run round in a loop, each as a sorted list of block indices.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: vec![None; self.blocks.len()],
+            low: vec![0; self.blocks.len()],
+            on_stack: vec![false; self.blocks.len()],
+            stack: vec![],
+            next_index: 0,
+            components: vec![],
+        };
+        for idx in 0..self.blocks.len() {
+            if tarjan.index[idx].is_none() {
+                tarjan.visit(idx);
+            }
+        }
+
+        let mut result: Vec<Vec<usize>> = tarjan
+            .components
+            .into_iter()
+            .filter(|c| c.len() > 1 || self.next_blocks(c[0]).contains(&c[0]))
+            .map(|mut c| {
+                c.sort_unstable();
+                c
+            })
+            .collect();
+        result.sort();
+        result
+    }
+
+    /// Graphviz source. Unreachable blocks are grey and dashed, blocks in a cycle are red.
+    pub fn to_dot(&self, program: &Program) -> String {
+        let reachable = self.reachable_blocks();
+        let in_cycle: BTreeSet<usize> = self.cycles().into_iter().flatten().collect();
+
+        let mut result = String::from("digraph program {\n");
+        result.push_str("    node [shape=box fontname=\"monospace\"];\n");
+        result.push_str("    exit [shape=doublecircle label=\"end\"];\n");
+        for (idx, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for pc in block.start..block.end {
+                label.push_str(&format!("{}: {}\\l", pc, program.instructions[pc]));
+            }
+            let style = if !reachable[idx] {
+                " style=dashed color=grey fontcolor=grey"
+            } else if in_cycle.contains(&idx) {
+                " color=red"
+            } else {
+                ""
+            };
+            result.push_str(&format!("    b{} [label=\"{}\"{}];\n", idx, label, style));
+        }
+
+        for (idx, block) in self.blocks.iter().enumerate() {
+            let branch = block.successors.len() > 1;
+            for (n, target) in block.successors.iter().enumerate() {
+                let to = match target {
+                    Target::Block(next) => format!("b{}", next),
+                    Target::Exit => "exit".to_owned(),
+                    Target::OutOfRange(pc) => {
+                        result.push_str(&format!(
+                            "    out{} [shape=octagon color=red label=\"pc {}\"];\n",
+                            idx, pc
+                        ));
+                        format!("out{}", idx)
+                    }
+                    Target::Anywhere => {
+                        result.push_str(&format!(
+                            "    any{} [shape=circle style=dashed label=\"?\"];\n",
+                            idx
+                        ));
+                        format!("any{}", idx)
+                    }
+                };
+                let label = match (branch, n) {
+                    (false, _) => "",
+                    (true, 0) => " [label=\"else\"]",
+                    (true, _) => " [label=\"jump\"]",
+                };
+                result.push_str(&format!("    b{} -> {}{};\n", idx, to, label));
+            }
+        }
+        result.push_str("}\n");
+        result
+    }
+}
+
+/// Tarjan's strongly connected components over the blocks.
+struct Tarjan<'a> {
+    graph: &'a ControlFlowGraph,
+    index: Vec<Option<usize>>,
+    low: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, idx: usize) {
+        self.index[idx] = Some(self.next_index);
+        self.low[idx] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(idx);
+        self.on_stack[idx] = true;
+
+        for next in self.graph.next_blocks(idx) {
+            match self.index[next] {
+                None => {
+                    self.visit(next);
+                    self.low[idx] = self.low[idx].min(self.low[next]);
+                }
+                Some(index) if self.on_stack[next] => self.low[idx] = self.low[idx].min(index),
+                Some(_) => (),
+            }
+        }
+
+        if Some(self.low[idx]) == self.index[idx] {
+            let mut component = vec![];
+            while let Some(member) = self.stack.pop() {
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == idx {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+impl Program {
+    pub fn cfg(&self) -> ControlFlowGraph {
+        ControlFlowGraph::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn smoke_blocks() -> Result<()> {
+        let program = Program::load_program("day8_smoke.txt")?;
+        let cfg = program.cfg();
+        let spans: Vec<(usize, usize)> = cfg.blocks().iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(vec![(0, 1), (1, 3), (3, 5), (5, 6), (6, 8), (8, 9)], spans);
+        assert_eq!(vec![Target::Block(4)], cfg.blocks()[1].successors);
+        assert_eq!(vec![Target::Exit], cfg.blocks()[5].successors);
+        assert_eq!(Some(&cfg.blocks()[2]), cfg.block_at(4));
+
+        let reachable: Vec<usize> = cfg.reachable().into_iter().collect();
+        assert_eq!(vec![0, 1, 2, 3, 4, 6, 7], reachable);
+        assert_eq!(vec![5, 8], cfg.unreachable());
+        assert_eq!(vec![vec![1, 2, 4]], cfg.cycles());
+        Ok(())
+    }
+
+    #[test]
+    fn branches() -> Result<()> {
+        let program = Program::parse(
+            "    .reg b
+                set a 3
+            loop: add a -1
+                jnz a loop
+                jmp +5
+                jmp b",
+        )?;
+        let cfg = program.cfg();
+        assert_eq!(
+            vec![Target::Block(2), Target::Block(1)],
+            cfg.blocks()[1].successors
+        );
+        assert_eq!(vec![Target::OutOfRange(8)], cfg.blocks()[2].successors);
+        // The register jump could jump to itself.
+        assert_eq!(vec![vec![1], vec![3]], cfg.cycles());
+        // Nothing reaches it, though.
+        assert_eq!(vec![4], cfg.unreachable());
+
+        // Which could go anywhere, so nothing is unreachable once it's in the way.
+        let cfg = Program::parse("jmp a\nset a 1\n")?.cfg();
+        assert!(cfg.unreachable().is_empty());
+        assert_eq!(vec![vec![0]], cfg.cycles());
+
+        let cfg = Program::parse("nop +0\njmp +9223372036854775807\n")?.cfg();
+        assert_eq!(
+            vec![Target::OutOfRange(i64::MAX)],
+            cfg.blocks()[0].successors
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dot() -> Result<()> {
+        let program = Program::load_program("day8_smoke.txt")?;
+        let dot = program.cfg().to_dot(&program);
+        assert!(dot.starts_with("digraph program {\n"));
+        assert!(dot.contains("    b1 [label=\"1: acc +1\\l2: jmp +4\\l\" color=red];\n"));
+        assert!(dot.contains("    b3 [label=\"5: acc -99\\l\" style=dashed"));
+        assert!(dot.contains("    b5 -> exit;\n"));
+        assert!(dot.ends_with("}\n"));
+
+        let program = Program::parse("jnz acc +3\n")?;
+        let dot = program.cfg().to_dot(&program);
+        assert!(dot.contains("    b0 -> exit [label=\"else\"];\n"));
+        assert!(dot.contains("    b0 -> out0 [label=\"jump\"];\n"));
+        Ok(())
+    }
+}
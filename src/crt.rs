@@ -0,0 +1,159 @@
+//! Chinese remainder theorem, for moduli which needn't be coprime.
+
+use std::fmt::Display;
+
+use anyhow::{anyhow, bail, Result};
+
+/// Returns `(g, x, y)` with `a * x + b * y = g`, the greatest common divisor of `a` and `b`.
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
+    }
+}
+
+/// `x ≡ residue (mod modulus)`, with `residue` below `modulus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Congruence {
+    pub residue: u64,
+    pub modulus: u64,
+}
+
+impl Display for Congruence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "x ≡ {} (mod {})", self.residue, self.modulus)
+    }
+}
+
+impl Congruence {
+    /// `residue` may be negative or past `modulus`.
+    pub fn new(residue: i64, modulus: u64) -> Result<Congruence> {
+        if modulus == 0 {
+            bail!("Modulus must be positive");
+        }
+        Ok(Congruence {
+            residue: (residue as i128).rem_euclid(modulus as i128) as u64,
+            modulus,
+        })
+    }
+
+    /// Everything satisfying both `self` and `other`, which is a single congruence
+    /// modulo their least common multiple, if anything satisfies both.
+    pub fn merge(self, other: Congruence) -> Result<Congruence> {
+        let (a1, m1) = (self.residue as i128, self.modulus as i128);
+        let (a2, m2) = (other.residue as i128, other.modulus as i128);
+        let (g, p, _) = extended_gcd(m1, m2);
+        if (a2 - a1) % g != 0 {
+            bail!("{} contradicts {}", other, self);
+        }
+
+        // m1 * p ≡ g (mod m2), so stepping a1 by m1 * p * (a2 - a1) / g reaches a2 mod m2.
+        let step = m2 / g;
+        let k = ((a2 - a1) / g).rem_euclid(step) as u128 * p.rem_euclid(step) as u128;
+        let k = (k % step as u128) as i128;
+        let overflow = || anyhow!("Combining {} and {} overflows", self, other);
+        let modulus = m1.checked_mul(step).ok_or_else(overflow)?;
+        if modulus > u64::MAX as i128 {
+            return Err(overflow());
+        }
+        let residue = m1
+            .checked_mul(k)
+            .and_then(|x| x.checked_add(a1))
+            .ok_or_else(overflow)?;
+        Ok(Congruence {
+            residue: residue.rem_euclid(modulus) as u64,
+            modulus: modulus as u64,
+        })
+    }
+
+    /// Solutions from `start` on, smallest first, until they no longer fit in a `u64`.
+    pub fn solutions_from(self, start: u64) -> impl Iterator<Item = u64> {
+        let offset = (self.residue as i128 - start as i128).rem_euclid(self.modulus as i128);
+        let first = start.checked_add(offset as u64);
+        std::iter::successors(first, move |x| x.checked_add(self.modulus))
+    }
+}
+
+/// All `x` satisfying every one of `congruences`. An empty system is `x ≡ 0 (mod 1)`.
+pub fn solve(congruences: &[Congruence]) -> Result<Congruence> {
+    congruences
+        .iter()
+        .try_fold(Congruence::new(0, 1)?, |acc, c| acc.merge(*c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn congruence(residue: i64, modulus: u64) -> Congruence {
+        Congruence::new(residue, modulus).unwrap()
+    }
+
+    #[test]
+    fn gcd() {
+        assert_eq!((6, -3, 2), extended_gcd(30, 48));
+        assert_eq!((1, 1, 0), extended_gcd(1, 0));
+        let (g, x, y) = extended_gcd(240, 46);
+        assert_eq!(2, g);
+        assert_eq!(g, 240 * x + 46 * y);
+    }
+
+    #[test]
+    fn coprime() -> Result<()> {
+        let solution = solve(&[congruence(2, 3), congruence(3, 5), congruence(2, 7)])?;
+        assert_eq!(congruence(23, 105), solution);
+        assert_eq!("x ≡ 23 (mod 105)", solution.to_string());
+        assert_eq!(congruence(0, 1), solve(&[])?);
+        Ok(())
+    }
+
+    #[test]
+    fn shared_factors() -> Result<()> {
+        assert_eq!(
+            congruence(10, 12),
+            solve(&[congruence(2, 4), congruence(4, 6)])?
+        );
+        assert_eq!(
+            congruence(5, 6),
+            solve(&[congruence(-1, 6), congruence(2, 3)])?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let e = solve(&[congruence(1, 4), congruence(2, 6)]).unwrap_err();
+        assert_eq!("x ≡ 2 (mod 6) contradicts x ≡ 1 (mod 4)", e.to_string());
+        assert!(Congruence::new(1, 0).is_err());
+
+        let big = congruence(1, 18446744073709551557);
+        assert!(big.merge(congruence(2, 4294967291)).is_err());
+        // Past even an i128 before it's reduced.
+        let e = congruence(1, u64::MAX)
+            .merge(congruence(2, u64::MAX - 1))
+            .unwrap_err();
+        assert!(e.to_string().ends_with("overflows"));
+    }
+
+    #[test]
+    fn solutions() {
+        let c = congruence(3, 7);
+        assert_eq!(
+            vec![3, 10, 17],
+            c.solutions_from(0).take(3).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![10, 17],
+            c.solutions_from(10).take(2).collect::<Vec<_>>()
+        );
+        // u64::MAX is 1 mod 7, so nothing past this one fits.
+        let c = congruence(2, 7);
+        assert_eq!(
+            vec![u64::MAX - 6],
+            c.solutions_from(u64::MAX - 6).collect::<Vec<_>>()
+        );
+        assert_eq!(0, c.solutions_from(u64::MAX - 5).count());
+    }
+}
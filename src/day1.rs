@@ -1,40 +1,40 @@
-#[cfg(test)]
-mod tests {
-    use anyhow::{anyhow, Result};
-    use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
 
-    use crate::*;
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
-    }
+use crate::Solver;
 
-    #[test]
-    fn day_1_1() -> Result<()> {
+pub struct Day1 {
+    entries: HashMap<i32, i32>,
+}
+
+impl Solver for Day1 {
+    type Part1 = i32;
+    type Part2 = i32;
+
+    fn parse(input: &str) -> Result<Day1> {
         let mut entries = HashMap::new();
-        for line in read_file("day1.txt")? {
-            let val: i32 = line?.parse()?;
-            entries.insert(val, 1 as i32);
+        for line in input.lines() {
+            let val: i32 = line.trim().parse()?;
+            entries.insert(val, 1i32);
         }
+        Ok(Day1 { entries })
+    }
+
+    fn part1(&self) -> Result<i32> {
+        let entries = &self.entries;
 
         for k in entries.keys() {
             let target = 2020 - k;
             if entries.contains_key(&target) {
-                println!("Day1.1: {}", k * (2020 - k));
-                return Ok(());
+                return Ok(k * (2020 - k));
             }
         }
 
         Err(anyhow!("No solution found"))
     }
 
-    #[test]
-    fn day_1_2() -> Result<()> {
-        let mut entries = HashMap::new();
-        for line in read_file("day1.txt")? {
-            let val: i32 = line?.parse()?;
-            entries.insert(val, 1 as i32);
-        }
+    fn part2(&self) -> Result<i32> {
+        let entries = &self.entries;
 
         for k1 in entries.keys() {
             if *k1 > 2020 {
@@ -46,8 +46,7 @@ mod tests {
                 }
                 let target = 2020 - k1 - k2;
                 if entries.contains_key(&target) {
-                    println!("Day1.2: {}", k1 * k2 * target);
-                    return Ok(());
+                    return Ok(k1 * k2 * target);
                 }
             }
         }
@@ -55,3 +54,26 @@ mod tests {
         Err(anyhow!("No solution found"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::answers::assert_answer;
+
+    #[test]
+    fn it_works() {
+        assert_eq!(2 + 2, 4);
+    }
+
+    #[test]
+    fn day_1_1() -> Result<()> {
+        assert_answer(1, 1, "day1.txt", Day1::load("day1.txt")?.part1()?);
+        Ok(())
+    }
+
+    #[test]
+    fn day_1_2() -> Result<()> {
+        assert_answer(1, 2, "day1.txt", Day1::load("day1.txt")?.part2()?);
+        Ok(())
+    }
+}
@@ -2,6 +2,8 @@ use std::collections::{HashMap, HashSet};
 
 use anyhow::{bail, Context, Result};
 
+use crate::{parse_numbers, Solver};
+
 fn find_diffs(adapters: &[i64]) -> Result<[u32; 3]> {
     let mut result = [0, 0, 0];
 
@@ -47,9 +49,34 @@ fn count_ways(adapters: &[i64]) -> Result<(i64, i64, HashMap<i64, i64>)> {
     Ok((laptop, *ways.get(&laptop).unwrap(), ways))
 }
 
+pub struct Day10 {
+    adapters: Vec<i64>,
+}
+
+impl Solver for Day10 {
+    type Part1 = u32;
+    type Part2 = i64;
+
+    fn parse(input: &str) -> Result<Day10> {
+        Ok(Day10 {
+            adapters: parse_numbers(input)?,
+        })
+    }
+
+    fn part1(&self) -> Result<u32> {
+        let gaps = find_diffs(&self.adapters)?;
+        Ok(gaps[0] * gaps[2])
+    }
+
+    fn part2(&self) -> Result<i64> {
+        Ok(count_ways(&self.adapters)?.1)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::answers::assert_answer;
     use crate::load_numbers;
 
     #[test]
@@ -70,7 +97,8 @@ mod tests {
     fn day10_1() -> Result<()> {
         let adapters = load_numbers("day10.txt")?;
         let gaps = find_diffs(&adapters)?;
-        println!("Day 10.1 gaps {:?}, answer {}", gaps, gaps[0] * gaps[2]);
+        // println!("Day 10.1 gaps {:?}, answer {}", gaps, gaps[0] * gaps[2]);
+        assert_answer(10, 1, "day10.txt", gaps[0] * gaps[2]);
 
         Ok(())
     }
@@ -95,10 +123,7 @@ mod tests {
 
     #[test]
     fn day10_2() -> Result<()> {
-        let adapters = load_numbers("day10.txt")?;
-
-        let answer = count_ways(&adapters)?;
-        println!("Day10.2 {}", answer.1);
+        assert_answer(10, 2, "day10.txt", Day10::load("day10.txt")?.part2()?);
 
         Ok(())
     }
@@ -1,18 +1,20 @@
-use crate::read_file;
+use crate::{
+    grid::{Cell, Grid, Neighborhood, Point},
+    read_input, Solver,
+};
 
 use anyhow::{bail, Result};
-use std::cmp::min;
-use std::fmt;
+use std::{collections::HashMap, fmt};
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
 enum SeatState {
     Floor,
     Empty,
     Occupied,
 }
 
-impl SeatState {
-    fn parse(s: char) -> Result<Self> {
+impl Cell for SeatState {
+    fn from_char(s: char) -> Result<Self> {
         Ok(match s {
             '.' => SeatState::Floor,
             'L' => SeatState::Empty,
@@ -20,282 +22,228 @@ impl SeatState {
             _ => bail!("Invalid code"),
         })
     }
+
+    fn to_char(&self) -> char {
+        match self {
+            SeatState::Floor => '.',
+            SeatState::Empty => 'L',
+            SeatState::Occupied => '#',
+        }
+    }
+}
+
+/// Decides which other seats a passenger pays attention to.
+trait Visibility {
+    fn count_occupied(&self, seats: &Grid<SeatState>, p: Point) -> usize;
+}
+
+/// Only the seats right next to `p`.
+struct Adjacent(Neighborhood);
+
+impl Visibility for Adjacent {
+    fn count_occupied(&self, seats: &Grid<SeatState>, p: Point) -> usize {
+        seats
+            .neighbors(p, self.0)
+            .filter(|(_, s)| **s == SeatState::Occupied)
+            .count()
+    }
+}
+
+/// The first seat visible in each direction, looking past the floor.
+struct LineOfSight(Neighborhood);
+
+impl Visibility for LineOfSight {
+    fn count_occupied(&self, seats: &Grid<SeatState>, p: Point) -> usize {
+        self.0
+            .offsets()
+            .iter()
+            .filter(|direction| {
+                seats
+                    .ray(p, **direction)
+                    .map(|(_, s)| s)
+                    .find(|s| **s != SeatState::Floor)
+                    == Some(&SeatState::Occupied)
+            })
+            .count()
+    }
+}
+
+impl<F: Fn(&Grid<SeatState>, Point) -> usize> Visibility for F {
+    fn count_occupied(&self, seats: &Grid<SeatState>, p: Point) -> usize {
+        self(seats, p)
+    }
+}
+
+/// An empty seat fills when at most `occupy` seats it sees are taken; an occupied one empties at `vacate` or more.
+struct Rules<V> {
+    visibility: V,
+    occupy: usize,
+    vacate: usize,
+}
+
+impl Rules<Adjacent> {
+    fn adjacent() -> Rules<Adjacent> {
+        Rules {
+            visibility: Adjacent(Neighborhood::Moore),
+            occupy: 0,
+            vacate: 4,
+        }
+    }
+}
+
+impl Rules<LineOfSight> {
+    fn line_of_sight() -> Rules<LineOfSight> {
+        Rules {
+            visibility: LineOfSight(Neighborhood::Moore),
+            occupy: 0,
+            vacate: 5,
+        }
+    }
+}
+
+impl<V: Visibility> Rules<V> {
+    fn next(&self, seats: &Grid<SeatState>, p: Point, seat: &SeatState) -> SeatState {
+        match seat {
+            SeatState::Floor => SeatState::Floor,
+            SeatState::Empty if self.visibility.count_occupied(seats, p) <= self.occupy => {
+                SeatState::Occupied
+            }
+            SeatState::Occupied if self.visibility.count_occupied(seats, p) >= self.vacate => {
+                SeatState::Empty
+            }
+            _ => seat.clone(),
+        }
+    }
 }
 
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Eq, Hash, Clone)]
 struct Ferry {
-    seats: Vec<Vec<SeatState>>,
-    height: usize,
-    width: usize,
+    seats: Grid<SeatState>,
 }
 
 impl fmt::Display for Ferry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for row in &self.seats {
-            for seat in row {
-                match seat {
-                    SeatState::Floor => write!(f, ".")?,
-                    SeatState::Empty => write!(f, "L")?,
-                    SeatState::Occupied => write!(f, "#")?,
-                }
-            }
-            writeln!(f)?;
+        write!(f, "{}", self.seats)
+    }
+}
+
+/// How a simulation ended.
+#[derive(PartialEq, Eq)]
+enum Outcome {
+    /// Nothing changes after `generations` steps.
+    Stable { generations: usize, ferry: Ferry },
+    /// The seating after `start` steps comes back every `period` steps.
+    Cycle {
+        start: usize,
+        period: usize,
+        ferry: Ferry,
+    },
+}
+
+impl Outcome {
+    fn ferry(&self) -> &Ferry {
+        match self {
+            Outcome::Stable { ferry, .. } => ferry,
+            Outcome::Cycle { ferry, .. } => ferry,
         }
-        Ok(())
     }
 }
 
 impl Ferry {
     fn load_file(file_name: &str) -> Result<Ferry> {
-        let mut seats = vec![];
-        let mut width = 0;
-        for line in read_file(file_name)? {
-            let mut row = vec![];
-            let line = line?;
-            let line = line.trim();
-            for c in line.chars() {
-                row.push(SeatState::parse(c)?);
-            }
-            width = row.len();
-            seats.push(row);
-        }
+        Ferry::parse(&read_input(file_name)?)
+    }
 
-        let height = seats.len();
+    fn parse(input: &str) -> Result<Ferry> {
         Ok(Ferry {
-            seats,
-            height,
-            width,
+            seats: input.parse()?,
         })
     }
 
     fn count_occupied(&self) -> u32 {
-        let mut result = 0;
-        for row in &self.seats {
-            for seat in row {
-                result += match seat {
-                    SeatState::Occupied => 1,
-                    _ => 0,
-                }
-            }
-        }
-        result
+        self.seats.count(|s| *s == SeatState::Occupied) as u32
     }
 
-    fn get(&self, row: i32, col: i32) -> &SeatState {
-        if row < 0 || col < 0 {
-            return &SeatState::Floor;
-        }
-        let row = row as usize;
-        let col = col as usize;
-        if let Some(row) = self.seats.get(row) {
-            row.get(col).unwrap_or(&SeatState::Empty)
-        } else {
-            &SeatState::Empty
-        }
+    fn step_with<V: Visibility>(&self, rules: &Rules<V>) -> Ferry {
+        let seats = self
+            .seats
+            .map_points(|p, seat| rules.next(&self.seats, p, seat));
+        Ferry { seats }
     }
 
-    fn get_context(&self, row: usize, col: usize) -> (&SeatState, u8) {
-        let row = row as i32;
-        let col = col as i32;
-
-        let mut occupied = 0;
-        let center = self.get(row, col);
-        for x_diff in -1..2i32 {
-            for y_diff in -1..2i32 {
-                if x_diff == 0 && y_diff == 0 {
-                    continue;
-                }
-                if self.get(row + x_diff, col + y_diff) == &SeatState::Occupied {
-                    occupied += 1;
-                }
-            }
-        }
+    fn step(&self) -> Ferry {
+        self.step_with(&Rules::adjacent())
+    }
 
-        (center, occupied)
+    fn step2(&self) -> Ferry {
+        self.step_with(&Rules::line_of_sight())
     }
 
-    fn get_context2(&self, row: usize, col: usize) -> (&SeatState, u8) {
-        let row = row as i32;
-        let col = col as i32;
-
-        let min_diff = min(self.height, self.width) as i32;
-        let center = self.get(row, col);
-        let mut occupied = 0;
-
-        // North
-        for diff in 1..self.height as i32 {
-            match self.get(row - diff, col) {
-                SeatState::Empty => break,
-                SeatState::Occupied => {
-                    occupied += 1;
-                    // println!("North");
-                    break;
-                }
-                SeatState::Floor => {}
-            }
-        }
-        // South
-        for diff in 1..self.height as i32 {
-            match self.get(row + diff, col) {
-                SeatState::Empty => break,
-                SeatState::Occupied => {
-                    occupied += 1;
-                    // println!("South");
-
-                    break;
-                }
-                SeatState::Floor => {}
-            }
-        }
-        // East
-        for diff in 1..self.width as i32 {
-            match self.get(row, col + diff) {
-                SeatState::Empty => break,
-                SeatState::Occupied => {
-                    occupied += 1;
-                    // println!("East");
-
-                    break;
-                }
-                SeatState::Floor => {}
-            }
-        }
-        // West
-        for diff in 1..self.width as i32 {
-            match self.get(row, col - diff) {
-                SeatState::Empty => break,
-                SeatState::Occupied => {
-                    occupied += 1;
-                    // println!("West");
-
-                    break;
-                }
-                SeatState::Floor => {}
-            }
-        }
-        // North-East
-        for diff in 1..min_diff {
-            match self.get(row - diff, col + diff) {
-                SeatState::Empty => break,
-                SeatState::Occupied => {
-                    occupied += 1;
-                    // println!("North-East");
-
-                    break;
-                }
-                SeatState::Floor => {}
-            }
-        }
-        // South-East
-        for diff in 1..min_diff {
-            match self.get(row + diff, col + diff) {
-                SeatState::Empty => break,
-                SeatState::Occupied => {
-                    occupied += 1;
-                    // println!("South-East");
-
-                    break;
-                }
-                SeatState::Floor => {}
-            }
-        }
-        // South-West
-        for diff in 1..min_diff {
-            match self.get(row + diff, col - diff) {
-                SeatState::Empty => break,
-                SeatState::Occupied => {
-                    occupied += 1;
-                    // println!("South-West");
-
-                    break;
-                }
-                SeatState::Floor => {}
+    /// Steps until the seating stops changing or repeats an earlier one.
+    fn run<V: Visibility>(&self, rules: &Rules<V>) -> Outcome {
+        let mut seen = HashMap::new();
+        let mut ferry = self.clone();
+        for generation in 0.. {
+            let next = ferry.step_with(rules);
+            if next == ferry {
+                return Outcome::Stable {
+                    generations: generation,
+                    ferry,
+                };
             }
-        }
-        // North-West
-        for diff in 1..min_diff {
-            match self.get(row - diff, col - diff) {
-                SeatState::Empty => break,
-                SeatState::Occupied => {
-                    occupied += 1;
-                    // println!("North-West");
-
-                    break;
-                }
-                SeatState::Floor => {}
+            if let Some(start) = seen.insert(ferry.clone(), generation) {
+                return Outcome::Cycle {
+                    start,
+                    period: generation - start,
+                    ferry,
+                };
             }
+            ferry = next;
         }
-
-        (center, occupied)
+        unreachable!()
     }
+}
 
-    fn step(&self) -> Ferry {
-        let mut next = vec![];
-
-        for row in 0..self.height {
-            let mut next_row = vec![];
-            for col in 0..self.width {
-                let ctx = self.get_context(row, col);
-                let new_seat = match ctx {
-                    (SeatState::Floor, _) => SeatState::Floor,
-                    (SeatState::Empty, 0) => SeatState::Occupied,
-                    (SeatState::Occupied, count) => {
-                        if count >= 4 {
-                            SeatState::Empty
-                        } else {
-                            SeatState::Occupied
-                        }
-                    }
-                    _ => ctx.0.clone(),
-                };
-                next_row.push(new_seat);
-            }
-            next.push(next_row);
-        }
+pub struct Day11 {
+    ferry: Ferry,
+}
 
-        Ferry {
-            seats: next,
-            height: self.height,
-            width: self.width,
-        }
+impl Solver for Day11 {
+    type Part1 = u32;
+    type Part2 = u32;
+
+    fn parse(input: &str) -> Result<Day11> {
+        Ok(Day11 {
+            ferry: Ferry::parse(input)?,
+        })
     }
 
-    fn step2(&self) -> Ferry {
-        let mut next = vec![];
-
-        for row in 0..self.height {
-            let mut next_row = vec![];
-            for col in 0..self.width {
-                let ctx = self.get_context2(row, col);
-                let new_seat = match ctx {
-                    (SeatState::Floor, _) => SeatState::Floor,
-                    (SeatState::Empty, 0) => SeatState::Occupied,
-                    (SeatState::Occupied, count) => {
-                        if count >= 5 {
-                            SeatState::Empty
-                        } else {
-                            SeatState::Occupied
-                        }
-                    }
-                    _ => ctx.0.clone(),
-                };
-                next_row.push(new_seat);
-            }
-            next.push(next_row);
-        }
+    fn part1(&self) -> Result<u32> {
+        settled(self.ferry.run(&Rules::adjacent()))
+    }
 
-        Ferry {
-            seats: next,
-            height: self.height,
-            width: self.width,
+    fn part2(&self) -> Result<u32> {
+        settled(self.ferry.run(&Rules::line_of_sight()))
+    }
+}
+
+fn settled(outcome: Outcome) -> Result<u32> {
+    match outcome {
+        Outcome::Stable { ferry, .. } => Ok(ferry.count_occupied()),
+        Outcome::Cycle { start, period, .. } => {
+            bail!(
+                "Seating never settles: period {} from step {}",
+                period,
+                start
+            )
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Ferry;
-    use anyhow::Result;
+    use super::*;
+    use crate::answers::assert_answer;
 
     #[test]
     fn day11_smoke1() -> Result<()> {
@@ -315,29 +263,20 @@ mod tests {
 
     #[test]
     fn day11_1() -> Result<()> {
-        let mut prev = Ferry::load_file("day11.txt")?;
-        loop {
-            // println!("Day11 smoke1 \n{}\n", prev);
-            let next = prev.step();
-            if next == prev {
-                break;
-            }
-            prev = next;
-        }
-
-        println!("Day 11.1: {}", prev.count_occupied());
+        assert_answer(11, 1, "day11.txt", Day11::load("day11.txt")?.part1()?);
         Ok(())
     }
 
     #[test]
     fn day11_smoke2() -> Result<()> {
         let mut prev = Ferry::load_file("day11_smoke.txt")?;
-        // println!("Day11 smoke2 ctx {:?}", prev.get_context2(1, 9));
         // prev = prev.step();
-        // println!("Day11 smoke2 ctx {:?}", prev.get_context2(1, 9));
 
         loop {
-            println!("Day11 smoke2 ctx {:?}", prev.get_context2(1, 9));
+            println!(
+                "Day11 smoke2 ctx {:?}",
+                LineOfSight(Neighborhood::Moore).count_occupied(&prev.seats, (9, 1))
+            );
             println!("Day11 smoke2 \n{}", prev);
             let next = prev.step2();
             if next == prev {
@@ -352,17 +291,73 @@ mod tests {
 
     #[test]
     fn day11_2() -> Result<()> {
-        let mut prev = Ferry::load_file("day11.txt")?;
-        loop {
-            // println!("Day11 smoke1 \n{}\n", prev);
-            let next = prev.step2();
-            if next == prev {
-                break;
+        assert_answer(11, 2, "day11.txt", Day11::load("day11.txt")?.part2()?);
+        Ok(())
+    }
+
+    #[test]
+    fn day11_generations() -> Result<()> {
+        let ferry = Ferry::load_file("day11_smoke.txt")?;
+        match ferry.run(&Rules::adjacent()) {
+            Outcome::Stable { generations, ferry } => {
+                assert_eq!(5, generations);
+                assert_eq!(37, ferry.count_occupied());
             }
-            prev = next;
+            _ => panic!("Expected the smoke test to settle"),
+        }
+        match ferry.run(&Rules::line_of_sight()) {
+            Outcome::Stable { generations, ferry } => {
+                assert_eq!(6, generations);
+                assert_eq!(26, ferry.count_occupied());
+            }
+            _ => panic!("Expected the smoke test to settle"),
         }
+        Ok(())
+    }
 
-        println!("Day 11.2: {}", prev.count_occupied());
+    #[test]
+    fn day11_custom_rules() -> Result<()> {
+        // Orthogonal neighbors only: everyone sits down, then the middle seat with four neighbors leaves.
+        let rules = Rules {
+            visibility: Adjacent(Neighborhood::Orthogonal),
+            occupy: 1,
+            vacate: 4,
+        };
+        let ferry = Ferry::parse("LLL\nLLL\nLLL\n")?;
+        let outcome = ferry.run(&rules);
+        assert_eq!(8, outcome.ferry().count_occupied());
+
+        // Any closure counting occupied seats will do.
+        let nobody = |_: &Grid<SeatState>, _: Point| 0;
+        let rules = Rules {
+            visibility: nobody,
+            occupy: 0,
+            vacate: 1,
+        };
+        assert!(matches!(
+            ferry.run(&rules),
+            Outcome::Stable { generations: 1, .. }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn day11_cycle() -> Result<()> {
+        // Two seats which both fill up while the other is empty, then both leave.
+        let rules = Rules {
+            visibility: Adjacent(Neighborhood::Orthogonal),
+            occupy: 0,
+            vacate: 1,
+        };
+        let ferry = Ferry::parse("LL\n")?;
+        match ferry.run(&rules) {
+            Outcome::Cycle { start, period, .. } => {
+                assert_eq!(0, start);
+                assert_eq!(2, period);
+            }
+            _ => panic!("Expected a cycle"),
+        }
+        assert!(settled(ferry.run(&rules)).is_err());
         Ok(())
     }
 }
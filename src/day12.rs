@@ -4,6 +4,8 @@ use anyhow::{bail, Context, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
 
+use crate::Solver;
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 enum Direction {
     North,
@@ -86,9 +88,12 @@ impl FromStr for Instruction {
 }
 
 fn load_route(file_name: &str) -> Result<Vec<Instruction>> {
+    parse_route(&crate::read_input(file_name)?)
+}
+
+fn parse_route(input: &str) -> Result<Vec<Instruction>> {
     let mut result = vec![];
-    for line in crate::read_file(file_name)? {
-        let line = line?;
+    for line in input.lines() {
         let line = line.trim();
         result.push(line.parse()?);
     }
@@ -205,9 +210,37 @@ impl Ship {
         Ok(())
     }
 }
+pub struct Day12 {
+    route: Vec<Instruction>,
+}
+
+impl Solver for Day12 {
+    type Part1 = i32;
+    type Part2 = i32;
+
+    fn parse(input: &str) -> Result<Day12> {
+        Ok(Day12 {
+            route: parse_route(input)?,
+        })
+    }
+
+    fn part1(&self) -> Result<i32> {
+        let mut ship = Ship::new();
+        ship.execute_route(&self.route)?;
+        Ok(ship.east.abs() + ship.north.abs())
+    }
+
+    fn part2(&self) -> Result<i32> {
+        let mut ship = Ship::new();
+        ship.execute_route2(&self.route)?;
+        Ok(ship.east.abs() + ship.north.abs())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::answers::assert_answer;
 
     #[test]
     fn day12_smoke1() -> Result<()> {
@@ -235,20 +268,14 @@ mod tests {
 
     #[test]
     fn day12_1() -> Result<()> {
-        let route = load_route("day12.txt")?;
-        let mut ship = Ship::new();
-        ship.execute_route(&route)?;
-        println!("Day 12.1: {}", ship.east.abs() + ship.north.abs());
+        assert_answer(12, 1, "day12.txt", Day12::load("day12.txt")?.part1()?);
 
         Ok(())
     }
 
     #[test]
     fn day12_2() -> Result<()> {
-        let route = load_route("day12.txt")?;
-        let mut ship = Ship::new();
-        ship.execute_route2(&route)?;
-        println!("Day 12.2: {}", ship.east.abs() + ship.north.abs());
+        assert_answer(12, 2, "day12.txt", Day12::load("day12.txt")?.part2()?);
 
         Ok(())
     }
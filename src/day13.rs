@@ -1,38 +1,56 @@
+use std::convert::TryFrom;
+
 use anyhow::{Context, Result};
 
-fn parse_busses(line: &str) -> Result<Vec<u64>> {
+use crate::{
+    crt::{self, Congruence},
+    Solver,
+};
+
+/// Bus ids, with None for each `x`.
+fn parse_busses(line: &str) -> Result<Vec<Option<u64>>> {
     line.split(',')
         .map(|item| {
             if item != "x" {
-                item.parse().context("Could not parse")
+                item.parse().map(Some).context("Could not parse")
             } else {
-                Ok(1)
+                Ok(None)
             }
         })
         .collect()
 }
 
-fn parse_problem(file_name: &str) -> Result<(u64, Vec<u64>)> {
-    let mut input = crate::read_file(file_name)?;
+fn load_problem(file_name: &str) -> Result<(u64, Vec<Option<u64>>)> {
+    parse_problem(&crate::read_input(file_name)?)
+}
+
+fn parse_problem(input: &str) -> Result<(u64, Vec<Option<u64>>)> {
+    let mut input = input.lines();
 
-    let line = input.next().context("No first line")??;
+    let line = input.next().context("No first line")?;
 
-    let earliest_time = line.parse()?;
-    let line = input.next().context("No second line")??;
-    let busses = parse_busses(&line)?;
+    let earliest_time = line.trim().parse()?;
+    let line = input.next().context("No second line")?;
+    let busses = parse_busses(line.trim())?;
 
     Ok((earliest_time, busses))
 }
 
-fn print_crt_problem(busses: &[u64]) {
-    for (idx, bus) in busses.iter().enumerate() {
-        if *bus == 0 {
-            continue;
-        }
-        let remainder = to_mod(idx as u64, *bus);
+/// The bus at index `idx` leaves `idx` minutes after the timestamp, so the timestamp is `-idx` mod the bus.
+fn congruences(busses: &[Option<u64>]) -> Result<Vec<Congruence>> {
+    busses
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, bus)| bus.map(|bus| (idx, bus)))
+        .map(|(idx, bus)| Congruence::new(-i64::try_from(idx)?, bus))
+        .collect()
+}
 
-        println!("x = {} mod {}", remainder, bus);
+fn print_crt_problem(busses: &[Option<u64>]) -> Result<()> {
+    for congruence in congruences(busses)? {
+        println!("{}", congruence);
     }
+    Ok(())
 }
 
 fn to_mod(idx: u64, bus: u64) -> u64 {
@@ -43,11 +61,16 @@ fn to_mod(idx: u64, bus: u64) -> u64 {
     result
 }
 
-fn crt_brute_force(nums: &[u64]) -> u64 {
+/// Only right for pairwise coprime bus ids.
+fn crt_brute_force(nums: &[Option<u64>]) -> u64 {
     let mut solution = 0;
     let mut step = 1u64;
 
     for (idx, bus) in nums.iter().enumerate() {
+        let bus = match bus {
+            Some(bus) => bus,
+            None => continue,
+        };
         let target = to_mod(idx as u64, *bus);
 
         while solution % bus != target {
@@ -59,27 +82,68 @@ fn crt_brute_force(nums: &[u64]) -> u64 {
     solution
 }
 
+fn best_bus(earliest_time: u64, busses: &[Option<u64>]) -> (u64, u64) {
+    let mut best_wait = u64::MAX;
+    let mut best_bus = 0;
+
+    for bus in busses.iter().flatten() {
+        let wait = to_mod(earliest_time, *bus);
+        if wait < best_wait {
+            best_wait = wait;
+            best_bus = *bus;
+        }
+    }
+    (best_bus, best_wait)
+}
+
+pub struct Day13 {
+    earliest_time: u64,
+    busses: Vec<Option<u64>>,
+}
+
+impl Day13 {
+    /// Every timestamp the busses leave in order, as a residue and modulus.
+    fn schedule(&self) -> Result<Congruence> {
+        crt::solve(&congruences(&self.busses)?)
+    }
+
+    /// The first `count` timestamps from `start` on when the busses leave in order.
+    fn timestamps(&self, start: u64, count: usize) -> Result<Vec<u64>> {
+        Ok(self.schedule()?.solutions_from(start).take(count).collect())
+    }
+}
+
+impl Solver for Day13 {
+    type Part1 = u64;
+    type Part2 = u64;
+
+    fn parse(input: &str) -> Result<Day13> {
+        let (earliest_time, busses) = parse_problem(input)?;
+        Ok(Day13 {
+            earliest_time,
+            busses,
+        })
+    }
+
+    fn part1(&self) -> Result<u64> {
+        let (best_bus, best_wait) = best_bus(self.earliest_time, &self.busses);
+        Ok(best_wait * best_bus)
+    }
+
+    fn part2(&self) -> Result<u64> {
+        Ok(self.schedule()?.residue)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn day13_smoke1() -> Result<()> {
-        let problem = parse_problem("day13_smoke.txt")?;
+        let problem = load_problem("day13_smoke.txt")?;
         // print_crt_problem(&problem.1);
-        let mut best_wait = u64::MAX;
-        let mut best_bus = 0;
-
-        for bus in problem.1 {
-            if bus == 1 {
-                continue;
-            }
-            let wait = to_mod(problem.0, bus);
-            if wait < best_wait {
-                best_wait = wait;
-                best_bus = bus;
-            }
-        }
+        let (best_bus, best_wait) = best_bus(problem.0, &problem.1);
         let soonest = problem.0 + best_wait;
         assert_eq!(59, best_bus);
         assert_eq!(944, soonest);
@@ -90,44 +154,72 @@ mod tests {
 
     #[test]
     fn day13_1() -> Result<()> {
-        let problem = parse_problem("day13.txt")?;
-        // print_crt_problem(&problem.1);
-        let mut best_wait = u64::MAX;
-        let mut best_bus = 0;
-
-        for bus in problem.1 {
-            if bus == 1 {
-                continue;
-            }
-            let wait = to_mod(problem.0, bus);
-            if wait < best_wait {
-                best_wait = wait;
-                best_bus = bus;
-            }
-        }
-        println!("Day 13.1: {}", best_wait * best_bus);
-        assert_eq!(119, best_wait * best_bus);
+        let answer = Day13::load("day13.txt")?.part1()?;
+        println!("Day 13.1: {}", answer);
+        assert_eq!(119, answer);
 
         Ok(())
     }
 
     #[test]
     fn day13_smoke2() -> Result<()> {
-        let problem = parse_problem("day13_smoke.txt")?;
+        let problem = load_problem("day13_smoke.txt")?;
         let answer = crt_brute_force(&problem.1);
 
         assert_eq!(1068781, answer);
+        assert_eq!(1068781, crt::solve(&congruences(&problem.1)?)?.residue);
 
         Ok(())
     }
 
     #[test]
     fn day13_2() -> Result<()> {
-        let problem = parse_problem("day13.txt")?;
-        let answer = crt_brute_force(&problem.1);
+        let answer = Day13::load("day13.txt")?.part2()?;
 
         println!("Day 13.2: {}", answer);
         assert_eq!(1106724616194525, answer);
         Ok(())
     }
+
+    #[test]
+    fn day13_examples() -> Result<()> {
+        let examples = [
+            ("17,x,13,19", 3417),
+            ("67,7,59,61", 754018),
+            ("67,x,7,59,61", 779210),
+            ("67,7,x,59,61", 1261476),
+            ("1789,37,47,1889", 1202161486),
+        ];
+        for (line, expected) in examples.iter() {
+            let day = Day13::parse(&format!("0\n{}\n", line))?;
+            assert_eq!(*expected, day.part2()?, "{}", line);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn day13_shared_factors() -> Result<()> {
+        // Leaving at t and t + 2 both work out as t ≡ 0 (mod 2).
+        let day = Day13::parse("0\n4,x,6\n")?;
+        let schedule = day.schedule()?;
+        assert_eq!(Congruence::new(4, 12)?, schedule);
+        assert_eq!(vec![16, 28, 40], day.timestamps(5, 3)?);
+
+        // t even but t + 1 a multiple of 6 can't happen.
+        let day = Day13::parse("0\n4,6\n")?;
+        let e = day.part2().unwrap_err();
+        assert_eq!("x ≡ 5 (mod 6) contradicts x ≡ 0 (mod 4)", e.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn day13_timestamps() -> Result<()> {
+        let day = Day13::load("day13_smoke.txt")?;
+        let period = 7 * 13 * 59 * 31 * 19;
+        assert_eq!(
+            vec![1068781, 1068781 + period, 1068781 + 2 * period],
+            day.timestamps(0, 3)?
+        );
+        Ok(())
+    }
 }
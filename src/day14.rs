@@ -5,6 +5,8 @@ use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
 
+use crate::Solver;
+
 #[derive(Debug, Clone)]
 struct BitMask {
     ones: u64,
@@ -113,9 +115,12 @@ impl fmt::Display for Instruction {
 }
 
 fn load_program(file_name: &str) -> Result<Vec<Instruction>> {
+    parse_program(&crate::read_input(file_name)?)
+}
+
+fn parse_program(input: &str) -> Result<Vec<Instruction>> {
     let mut result = vec![];
-    for line in crate::read_file(file_name)? {
-        let line = line?;
+    for line in input.lines() {
         let line = line.trim();
         result.push(line.parse()?);
     }
@@ -164,9 +169,37 @@ impl Computer {
     }
 }
 
+pub struct Day14 {
+    program: Vec<Instruction>,
+}
+
+impl Solver for Day14 {
+    type Part1 = u64;
+    type Part2 = u64;
+
+    fn parse(input: &str) -> Result<Day14> {
+        Ok(Day14 {
+            program: parse_program(input)?,
+        })
+    }
+
+    fn part1(&self) -> Result<u64> {
+        let mut computer = Computer::new();
+        computer.execute(&self.program);
+        Ok(computer.sum())
+    }
+
+    fn part2(&self) -> Result<u64> {
+        let mut computer = Computer::new();
+        computer.execute2(&self.program);
+        Ok(computer.sum())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::answers::assert_answer;
 
     #[test]
     fn day14_smoke1() -> Result<()> {
@@ -183,10 +216,7 @@ mod tests {
 
     #[test]
     fn day14_1() -> Result<()> {
-        let program = load_program("day14.txt")?;
-        let mut computer = Computer::new();
-        computer.execute(&program);
-        println!("Day 14.1: {}", computer.sum());
+        assert_answer(14, 1, "day14.txt", Day14::load("day14.txt")?.part1()?);
 
         Ok(())
     }
@@ -203,10 +233,7 @@ mod tests {
 
     #[test]
     fn day14_2() -> Result<()> {
-        let program = load_program("day14.txt")?;
-        let mut computer = Computer::new();
-        computer.execute2(&program);
-        println!("Day 14.2: {}", computer.sum());
+        assert_answer(14, 2, "day14.txt", Day14::load("day14.txt")?.part2()?);
 
         Ok(())
     }
@@ -1,5 +1,9 @@
 use std::collections::HashMap;
 
+use anyhow::{bail, Context, Result};
+
+use crate::Solver;
+
 fn start(nums: &[u32]) -> HashMap<u32, usize> {
     let mut result = HashMap::new();
     for (idx, n) in nums.iter().enumerate() {
@@ -24,17 +28,47 @@ fn get_value_on_turn(seed: &[u32], target_turn: usize) -> u32 {
     let start = seed.len() + 1;
     let mut prev = *seed.last().unwrap();
     for turn in start..target_turn + 1 {
-        if turn % 10000 == 0 {
-            println!("Turn {}", turn);
-        }
+        // if turn % 10000 == 0 {
+        //     println!("Turn {}", turn);
+        // }
         prev = next_number(&mut state, turn, prev);
     }
     prev
 }
 
+pub struct Day15 {
+    seed: Vec<u32>,
+}
+
+impl Solver for Day15 {
+    type Part1 = u32;
+    type Part2 = u32;
+
+    fn parse(input: &str) -> Result<Day15> {
+        let seed = input
+            .trim()
+            .split(',')
+            .map(|n| n.trim().parse().context("Bad number"))
+            .collect::<Result<Vec<u32>>>()?;
+        if seed.is_empty() {
+            bail!("No starting numbers");
+        }
+        Ok(Day15 { seed })
+    }
+
+    fn part1(&self) -> Result<u32> {
+        Ok(get_value_on_turn(&self.seed, 2020))
+    }
+
+    fn part2(&self) -> Result<u32> {
+        Ok(get_value_on_turn(&self.seed, 30000000))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::answers::assert_answer;
 
     #[test]
     fn day15_smoke1() {
@@ -45,13 +79,17 @@ mod tests {
 
     #[test]
     fn day15_1() {
-        println!(
-            "Day 15.1: {}",
-            get_value_on_turn(&[11, 0, 1, 10, 5, 19], 2020)
+        assert_answer(
+            15,
+            1,
+            "day15.txt",
+            get_value_on_turn(&[11, 0, 1, 10, 5, 19], 2020),
         );
-        println!(
-            "Day 15.2: {}",
-            get_value_on_turn(&[11, 0, 1, 10, 5, 19], 30000000)
+        assert_answer(
+            15,
+            2,
+            "day15.txt",
+            get_value_on_turn(&[11, 0, 1, 10, 5, 19], 30000000),
         );
     }
 }
@@ -1,8 +1,10 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::{collections::HashMap, collections::HashSet, str::FromStr};
 
+use crate::Solver;
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 struct FieldRule {
     range1: (i32, i32),
@@ -72,12 +74,16 @@ struct Input {
 
 impl Input {
     fn load(file_name: &str) -> Result<Input> {
+        Input::parse(&crate::read_input(file_name)?)
+    }
+
+    fn parse(input: &str) -> Result<Input> {
         let mut fields = HashMap::new();
         let mut other_tickets = vec![];
 
-        let mut lines = crate::read_file(file_name)?;
+        let mut lines = input.lines();
         loop {
-            let line = lines.next().context("Missing line")??;
+            let line = lines.next().context("Missing line")?;
             let line = line.trim();
             if line.is_empty() {
                 break;
@@ -86,20 +92,22 @@ impl Input {
             fields.insert(rule.name.to_owned(), rule);
         }
 
-        lines.next().context("Missing label line")??; // Discard this
+        lines.next().context("Missing label line")?; // Discard this
 
         let your_ticket = lines
             .next()
-            .context("Missing line?")??
+            .context("Missing line?")?
             .split(',')
             .map(|n| n.parse().context("Bad number"))
             .collect::<Result<Vec<i32>>>()?;
 
-        lines.next().context("Missing blank line")??; // Discard this
-        lines.next().context("Missing label line")??; // Discard this
+        lines.next().context("Missing blank line")?; // Discard this
+        lines.next().context("Missing label line")?; // Discard this
 
-        for l in lines {
-            let line = l?;
+        for line in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
             other_tickets.push(
                 line.split(',')
                     .map(|n| n.parse().context("Bad number"))
@@ -147,55 +155,40 @@ fn simplify_uniques(possible_rules: &mut Vec<HashSet<&FieldRule>>) {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use anyhow::bail;
-    use std::{collections::HashSet, vec};
-
-    #[test]
-    fn day16_smoke1() -> Result<()> {
-        let input = Input::load("day16_smoke.txt")?;
-
-        assert!(is_ticket_valid(&input.your_ticket, &input.fields));
-        let mut result = 0;
+pub struct Day16 {
+    input: Input,
+}
 
-        for t in input.other_tickets {
-            for n in t {
-                if !is_field_valid(n, &input.fields) {
-                    result += n;
-                }
-            }
-        }
-        assert_eq!(71, result);
+impl Solver for Day16 {
+    type Part1 = i32;
+    type Part2 = i64;
 
-        Ok(())
+    fn parse(input: &str) -> Result<Day16> {
+        Ok(Day16 {
+            input: Input::parse(input)?,
+        })
     }
 
-    #[test]
-    fn day16_1() -> Result<()> {
-        let input = Input::load("day16.txt")?;
-
-        assert!(is_ticket_valid(&input.your_ticket, &input.fields));
+    fn part1(&self) -> Result<i32> {
+        let input = &self.input;
         let mut result = 0;
 
-        for t in input.other_tickets {
+        for t in &input.other_tickets {
             for n in t {
-                if !is_field_valid(n, &input.fields) {
+                if !is_field_valid(*n, &input.fields) {
                     result += n;
                 }
             }
         }
-        println!("Day 16.1: {}", result);
-        assert_eq!(25916, result);
-        Ok(())
+        Ok(result)
     }
 
-    #[test]
-    fn day16_2() -> Result<()> {
-        let input = Input::load("day16.txt")?;
+    fn part2(&self) -> Result<i64> {
+        let input = &self.input;
 
-        assert!(is_ticket_valid(&input.your_ticket, &input.fields));
+        if !is_ticket_valid(&input.your_ticket, &input.fields) {
+            bail!("Your ticket is invalid");
+        }
 
         let valid_tickets: Vec<Vec<i32>> = input
             .other_tickets
@@ -217,7 +210,7 @@ mod tests {
         //     println!("Field {} = {:?}", idx, possible);
         // }
         // println!();
-        for (_ticket_num, t) in valid_tickets.iter().enumerate() {
+        for t in valid_tickets.iter() {
             // println!("Ticket {}", ticket_num);
             for (idx, num) in t.iter().enumerate() {
                 let mut to_remove = vec![];
@@ -258,6 +251,48 @@ mod tests {
             }
         }
 
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day16_smoke1() -> Result<()> {
+        let input = Input::load("day16_smoke.txt")?;
+
+        assert!(is_ticket_valid(&input.your_ticket, &input.fields));
+        let mut result = 0;
+
+        for t in input.other_tickets {
+            for n in t {
+                if !is_field_valid(n, &input.fields) {
+                    result += n;
+                }
+            }
+        }
+        assert_eq!(71, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn day16_1() -> Result<()> {
+        let input = Input::load("day16.txt")?;
+
+        assert!(is_ticket_valid(&input.your_ticket, &input.fields));
+        let result = Day16::load("day16.txt")?.part1()?;
+        println!("Day 16.1: {}", result);
+        assert_eq!(25916, result);
+        Ok(())
+    }
+
+    #[test]
+    fn day16_2() -> Result<()> {
+        let result = Day16::load("day16.txt")?.part2()?;
+
         println!("Day 16.2: {}", result);
         assert_eq!(2564529489989, result);
 
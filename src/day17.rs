@@ -1,340 +1,285 @@
 use std::{
-    cmp::{max, min},
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Display,
+    str::FromStr,
 };
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Error, Result};
 
-type Coordinate = (i32, i32, i32);
+use crate::{
+    grid::{self, Grid},
+    Solver,
+};
 
-#[derive(PartialEq, Eq)]
-enum CubeState {
-    Active,
-    Inactive,
+/// Life-like rule: how many active neighbors bring an inactive cube to life, and keep an active one alive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    birth: Vec<usize>,
+    survival: Vec<usize>,
 }
 
-impl Display for CubeState {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        match self {
-            CubeState::Active => write!(f, "#"),
-            CubeState::Inactive => write!(f, "."),
+impl Rule {
+    /// Only active cubes are stored, so births from no neighbors at all (B0) aren't supported.
+    pub fn new(birth: &[usize], survival: &[usize]) -> Result<Rule> {
+        if birth.contains(&0) {
+            bail!("B0 rules are not supported");
         }
+        Ok(Rule {
+            birth: birth.to_vec(),
+            survival: survival.to_vec(),
+        })
     }
-}
 
-impl CubeState {
-    fn from(c: char) -> Result<CubeState> {
-        match c {
-            '.' => Ok(CubeState::Inactive),
-            '#' => Ok(CubeState::Active),
-            _ => bail!("Invalid symbol"),
+    /// B3/S23, the rule from the puzzle.
+    pub fn conway() -> Rule {
+        Rule {
+            birth: vec![3],
+            survival: vec![2, 3],
         }
     }
-}
-
-struct World {
-    cubes: HashMap<Coordinate, CubeState>,
-    x_limits: (i32, i32),
-    y_limits: (i32, i32),
-    z_limits: (i32, i32),
-}
 
-struct HyperWorld {
-    cubes: HashMap<(i32, i32, i32, i32), CubeState>,
-    x_limits: (i32, i32),
-    y_limits: (i32, i32),
-    z_limits: (i32, i32),
-    w_limits: (i32, i32),
+    fn next(&self, active: bool, neighbors: usize) -> bool {
+        if active {
+            self.survival.contains(&neighbors)
+        } else {
+            self.birth.contains(&neighbors)
+        }
+    }
 }
 
-impl Display for World {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for z in self.z_limits.0..self.z_limits.1 + 1 {
-            writeln!(f, "z={}", z)?;
-            for y in self.y_limits.0..self.y_limits.1 + 1 {
-                for x in self.x_limits.0..self.x_limits.1 + 1 {
-                    let cube = self.cubes.get(&(x, y, z)).unwrap_or(&CubeState::Inactive);
-                    write!(f, "{}", cube)?;
-                }
-                writeln!(f)?;
+/// Counts are single digits (`B36/S23`) or, for larger neighborhoods, comma separated (`B3,12/S2,3`).
+impl FromStr for Rule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Rule> {
+        fn counts(s: &str) -> Result<Vec<usize>> {
+            if s.contains(',') {
+                s.split(',')
+                    .map(|c| c.trim().parse().context("Invalid count"))
+                    .collect()
+            } else {
+                s.chars()
+                    .map(|c| c.to_digit(10).map(|d| d as usize).context("Invalid count"))
+                    .collect()
             }
-            writeln!(f)?;
         }
-        Ok(())
+
+        let mut parts = s.trim().split('/');
+        let (birth, survival) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(b), Some(s), None) => (b, s),
+            _ => bail!("Rule must look like B3/S23: {}", s),
+        };
+        let birth = birth
+            .strip_prefix('B')
+            .or_else(|| birth.strip_prefix('b'))
+            .with_context(|| format!("Missing B in {}", s))?;
+        let survival = survival
+            .strip_prefix('S')
+            .or_else(|| survival.strip_prefix('s'))
+            .with_context(|| format!("Missing S in {}", s))?;
+        Rule::new(&counts(birth)?, &counts(survival)?)
     }
 }
 
-impl Display for HyperWorld {
+impl Display for Rule {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for w in self.w_limits.0..self.w_limits.1 + 1 {
-            for z in self.z_limits.0..self.z_limits.1 + 1 {
-                writeln!(f, "z={}, w={}", z, w)?;
-                for y in self.y_limits.0..self.y_limits.1 + 1 {
-                    for x in self.x_limits.0..self.x_limits.1 + 1 {
-                        let cube = self
-                            .cubes
-                            .get(&(x, y, z, w))
-                            .unwrap_or(&CubeState::Inactive);
-                        write!(f, "{}", cube)?;
-                    }
-                    writeln!(f)?;
-                }
-                writeln!(f)?;
-            }
-        }
-        Ok(())
+        let wide = self.birth.iter().chain(&self.survival).any(|c| *c > 9);
+        let join = |counts: &[usize]| {
+            let counts: Vec<String> = counts.iter().map(|c| c.to_string()).collect();
+            counts.join(if wide { "," } else { "" })
+        };
+        write!(f, "B{}/S{}", join(&self.birth), join(&self.survival))
     }
 }
 
-impl World {
-    fn load(file_name: &str) -> Result<World> {
-        let mut cubes = HashMap::new();
-        let mut x_limits = (0, 0);
-        let mut y_limits = (0, 0);
-        let z_limits = (0, 0);
-
-        for (y, line) in crate::read_file(file_name)?.enumerate() {
-            y_limits.1 = max(y as i32, y_limits.1);
-            let line = line?;
-            let line = line.trim();
-            for (x, c) in line.chars().enumerate() {
-                let coord = (x as i32, y as i32, 0);
-                x_limits.1 = max(x as i32, x_limits.1);
-                let cube = CubeState::from(c)?;
-                cubes.insert(coord, cube);
-            }
-        }
+/// Pocket dimension with `D` axes. Only active cubes are stored.
+#[derive(Clone)]
+pub struct NWorld<const D: usize> {
+    active: HashSet<[i32; D]>,
+    rule: Rule,
+    offsets: Vec<[i32; D]>,
+}
 
-        Ok(World {
-            cubes,
-            x_limits,
-            y_limits,
-            z_limits,
-        })
-    }
+const AXES: [&str; 4] = ["x", "y", "z", "w"];
 
-    fn step(&mut self) {
-        let mut cubes = HashMap::new();
-        let mut x_limits = (i32::MAX, i32::MIN);
-        let mut y_limits = (i32::MAX, i32::MIN);
-        let mut z_limits = (i32::MAX, i32::MIN);
-
-        for z in self.z_limits.0 - 1..self.z_limits.1 + 2 {
-            for y in self.y_limits.0 - 1..self.y_limits.1 + 2 {
-                for x in self.x_limits.0 - 1..self.x_limits.1 + 2 {
-                    let coor = (x, y, z);
-                    let neighbor_count = self.neighbor_count(&coor);
-                    // println!("DEBUG: {:?} => {}", coor, neighbor_count);
-                    let old_state = self.get_cube_coor(&coor);
-                    let new_state = match old_state {
-                        CubeState::Active => {
-                            if neighbor_count == 2 || neighbor_count == 3 {
-                                CubeState::Active
-                            } else {
-                                CubeState::Inactive
-                            }
-                        }
-                        CubeState::Inactive => {
-                            if neighbor_count == 3 {
-                                CubeState::Active
-                            } else {
-                                CubeState::Inactive
-                            }
-                        }
-                    };
-                    if new_state == CubeState::Active {
-                        x_limits.0 = min(x_limits.0, x);
-                        x_limits.1 = max(x_limits.1, x);
-                        y_limits.0 = min(y_limits.0, y);
-                        y_limits.1 = max(y_limits.1, y);
-                        z_limits.0 = min(z_limits.0, z);
-                        z_limits.1 = max(z_limits.1, z);
-                        cubes.insert(coor, new_state);
-                    }
+impl<const D: usize> Display for NWorld<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (min, max) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return Ok(()),
+        };
+
+        // Every combination of the axes past x and y gets its own slice.
+        let mut layer = min;
+        loop {
+            let labels: Vec<String> = (2..D)
+                .map(|axis| match AXES.get(axis) {
+                    Some(name) => format!("{}={}", name, layer[axis]),
+                    None => format!("d{}={}", axis, layer[axis]),
+                })
+                .collect();
+            if !labels.is_empty() {
+                writeln!(f, "{}", labels.join(", "))?;
+            }
+            let slice = grid::render((min[0], min[1]), (max[0], max[1]), |(x, y)| {
+                let mut cube = layer;
+                cube[0] = x;
+                cube[1] = y;
+                if self.active.contains(&cube) {
+                    '#'
+                } else {
+                    '.'
                 }
+            });
+            writeln!(f, "{}", slice)?;
+
+            let mut axis = 2;
+            while axis < D && layer[axis] == max[axis] {
+                layer[axis] = min[axis];
+                axis += 1;
+            }
+            if axis >= D {
+                return Ok(());
             }
+            layer[axis] += 1;
         }
+    }
+}
 
-        self.cubes = cubes;
-        self.x_limits = x_limits;
-        self.y_limits = y_limits;
-        self.z_limits = z_limits;
+impl<const D: usize> NWorld<D> {
+    pub fn load(file_name: &str) -> Result<NWorld<D>> {
+        NWorld::parse(&crate::read_input(file_name)?)
     }
 
-    fn get_cube(&self, x: i32, y: i32, z: i32) -> &CubeState {
-        self.get_cube_coor(&(x, y, z))
+    /// The input is the `x`/`y` slice at 0 on every other axis.
+    pub fn parse(input: &str) -> Result<NWorld<D>> {
+        if D < 2 {
+            bail!("Need at least two dimensions, not {}", D);
+        }
+        let slice: Grid<bool> = input.parse()?;
+        let active = slice
+            .iter()
+            .filter(|(_, active)| **active)
+            .map(|((x, y), _)| {
+                let mut cube = [0; D];
+                cube[0] = x;
+                cube[1] = y;
+                cube
+            })
+            .collect();
+
+        Ok(NWorld {
+            active,
+            rule: Rule::conway(),
+            offsets: neighbor_offsets(),
+        })
     }
 
-    fn get_cube_coor(&self, coor: &Coordinate) -> &CubeState {
-        self.cubes.get(coor).unwrap_or(&CubeState::Inactive)
+    pub fn with_rule(mut self, rule: Rule) -> NWorld<D> {
+        self.rule = rule;
+        self
     }
 
-    fn neighbor_count(&self, coor: &Coordinate) -> u8 {
-        let mut result = 0;
-        for z_diff in -1..2 {
-            for y_diff in -1..2 {
-                for x_diff in -1..2 {
-                    if z_diff == 0 && y_diff == 0 && x_diff == 0 {
-                        continue;
-                    }
-                    let cube = self.get_cube(coor.0 + x_diff, coor.1 + y_diff, coor.2 + z_diff);
-                    result += match cube {
-                        CubeState::Active => 1,
-                        CubeState::Inactive => 0,
-                    }
+    pub fn step(&mut self) {
+        // Active cubes start at 0 so that ones without active neighbors still face the rule.
+        let mut counts: HashMap<[i32; D], usize> =
+            self.active.iter().map(|cube| (*cube, 0)).collect();
+        for cube in &self.active {
+            for offset in &self.offsets {
+                let mut neighbor = *cube;
+                for axis in 0..D {
+                    neighbor[axis] += offset[axis];
                 }
+                *counts.entry(neighbor).or_insert(0) += 1;
             }
         }
-        result
-    }
 
-    fn count_active(&self) -> u32 {
-        let mut result = 0;
-        for cube in self.cubes.values() {
-            if cube == &CubeState::Active {
-                result += 1;
-            }
-        }
-        result
+        // Inactive cubes without active neighbors aren't in `counts`, so they had better not be born.
+        let active = counts
+            .into_iter()
+            .filter(|(cube, count)| self.rule.next(self.active.contains(cube), *count))
+            .map(|(cube, _)| cube)
+            .collect();
+        self.active = active;
     }
-}
 
-impl HyperWorld {
-    fn load(file_name: &str) -> Result<HyperWorld> {
-        let mut cubes = HashMap::new();
-        let mut x_limits = (0, 0);
-        let mut y_limits = (0, 0);
-        let z_limits = (0, 0);
-        let w_limits = (0, 0);
-
-        for (y, line) in crate::read_file(file_name)?.enumerate() {
-            y_limits.1 = max(y as i32, y_limits.1);
-            let line = line?;
-            let line = line.trim();
-            for (x, c) in line.chars().enumerate() {
-                let coord = (x as i32, y as i32, 0, 0);
-                x_limits.1 = max(x as i32, x_limits.1);
-                let cube = CubeState::from(c)?;
-                cubes.insert(coord, cube);
-            }
+    pub fn run(&mut self, cycles: usize) {
+        for _cycle in 0..cycles {
+            self.step();
         }
+    }
 
-        Ok(HyperWorld {
-            cubes,
-            x_limits,
-            y_limits,
-            z_limits,
-            w_limits,
-        })
+    pub fn count_active(&self) -> usize {
+        self.active.len()
     }
 
-    fn step(&mut self) {
-        let mut cubes = HashMap::new();
-        let mut x_limits = (i32::MAX, i32::MIN);
-        let mut y_limits = (i32::MAX, i32::MIN);
-        let mut z_limits = (i32::MAX, i32::MIN);
-        let mut w_limits = (i32::MAX, i32::MIN);
-
-        for w in self.w_limits.0 - 1..self.w_limits.1 + 2 {
-            for z in self.z_limits.0 - 1..self.z_limits.1 + 2 {
-                for y in self.y_limits.0 - 1..self.y_limits.1 + 2 {
-                    for x in self.x_limits.0 - 1..self.x_limits.1 + 2 {
-                        let coor = (x, y, z, w);
-                        let neighbor_count = self.neighbor_count(&coor);
-                        // println!("DEBUG: {:?} => {}", coor, neighbor_count);
-                        let old_state = self.get_cube_coor(&coor);
-                        let new_state = match old_state {
-                            CubeState::Active => {
-                                if neighbor_count == 2 || neighbor_count == 3 {
-                                    CubeState::Active
-                                } else {
-                                    CubeState::Inactive
-                                }
-                            }
-                            CubeState::Inactive => {
-                                if neighbor_count == 3 {
-                                    CubeState::Active
-                                } else {
-                                    CubeState::Inactive
-                                }
-                            }
-                        };
-                        if new_state == CubeState::Active {
-                            x_limits.0 = min(x_limits.0, x);
-                            x_limits.1 = max(x_limits.1, x);
-                            y_limits.0 = min(y_limits.0, y);
-                            y_limits.1 = max(y_limits.1, y);
-                            z_limits.0 = min(z_limits.0, z);
-                            z_limits.1 = max(z_limits.1, z);
-                            w_limits.0 = min(w_limits.0, w);
-                            w_limits.1 = max(w_limits.1, w);
-                            cubes.insert(coor, new_state);
-                        }
-                    }
-                }
+    pub fn bounds(&self) -> Option<([i32; D], [i32; D])> {
+        let mut cubes = self.active.iter();
+        let first = *cubes.next()?;
+        Some(cubes.fold((first, first), |(mut min, mut max), cube| {
+            for axis in 0..D {
+                min[axis] = min[axis].min(cube[axis]);
+                max[axis] = max[axis].max(cube[axis]);
             }
-        }
-
-        self.cubes = cubes;
-        self.x_limits = x_limits;
-        self.y_limits = y_limits;
-        self.z_limits = z_limits;
-        self.w_limits = w_limits;
+            (min, max)
+        }))
     }
+}
 
-    fn get_cube(&self, x: i32, y: i32, z: i32, w: i32) -> &CubeState {
-        self.get_cube_coor(&(x, y, z, w))
+/// Every offset in {-1, 0, 1}^D except the origin.
+fn neighbor_offsets<const D: usize>() -> Vec<[i32; D]> {
+    let mut result = vec![];
+    for n in 0..3usize.pow(D as u32) {
+        let mut offset = [0; D];
+        let mut rest = n;
+        for value in offset.iter_mut() {
+            *value = (rest % 3) as i32 - 1;
+            rest /= 3;
+        }
+        if offset.iter().any(|v| *v != 0) {
+            result.push(offset);
+        }
     }
+    result
+}
 
-    fn get_cube_coor(&self, coor: &(i32, i32, i32, i32)) -> &CubeState {
-        self.cubes.get(coor).unwrap_or(&CubeState::Inactive)
+pub struct Day17 {
+    world: NWorld<3>,
+    hyper_world: NWorld<4>,
+}
+
+impl Solver for Day17 {
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Day17> {
+        Ok(Day17 {
+            world: NWorld::parse(input)?,
+            hyper_world: NWorld::parse(input)?,
+        })
     }
 
-    fn neighbor_count(&self, coor: &(i32, i32, i32, i32)) -> u8 {
-        let mut result = 0;
-        for w_diff in -1..2 {
-            for z_diff in -1..2 {
-                for y_diff in -1..2 {
-                    for x_diff in -1..2 {
-                        if z_diff == 0 && y_diff == 0 && x_diff == 0 && w_diff == 0 {
-                            continue;
-                        }
-                        let cube = self.get_cube(
-                            coor.0 + x_diff,
-                            coor.1 + y_diff,
-                            coor.2 + z_diff,
-                            coor.3 + w_diff,
-                        );
-                        result += match cube {
-                            CubeState::Active => 1,
-                            CubeState::Inactive => 0,
-                        }
-                    }
-                }
-            }
-        }
-        result
+    fn part1(&self) -> Result<usize> {
+        let mut world = self.world.clone();
+        world.run(6);
+        Ok(world.count_active())
     }
 
-    fn count_active(&self) -> u32 {
-        let mut result = 0;
-        for cube in self.cubes.values() {
-            if cube == &CubeState::Active {
-                result += 1;
-            }
-        }
-        result
+    fn part2(&self) -> Result<usize> {
+        let mut world = self.hyper_world.clone();
+        world.run(6);
+        Ok(world.count_active())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::answers::assert_answer;
 
     #[test]
     fn day17_smoke1() -> Result<()> {
-        let mut world = World::load("day17_smoke.txt")?;
+        let mut world = NWorld::<3>::load("day17_smoke.txt")?;
         println!("{}", world);
         for _cycle in 0..6 {
             world.step();
@@ -347,20 +292,13 @@ mod tests {
 
     #[test]
     fn day17_1() -> Result<()> {
-        let mut world = World::load("day17.txt")?;
-        println!("{}", world);
-        for _cycle in 0..6 {
-            world.step();
-            // println!("After {} cycle(s)\n{}\n", _cycle + 1, world);
-        }
-
-        println!("Day 17.1: {}", world.count_active());
+        assert_answer(17, 1, "day17.txt", Day17::load("day17.txt")?.part1()?);
         Ok(())
     }
 
     #[test]
     fn day17_smoke2() -> Result<()> {
-        let mut world = HyperWorld::load("day17_smoke.txt")?;
+        let mut world = NWorld::<4>::load("day17_smoke.txt")?;
         println!("{}", world);
         for _cycle in 0..6 {
             world.step();
@@ -373,14 +311,73 @@ mod tests {
 
     #[test]
     fn day17_2() -> Result<()> {
-        let mut world = HyperWorld::load("day17.txt")?;
-        println!("{}", world);
-        for _cycle in 0..6 {
-            world.step();
-            // println!("After {} cycle(s)\n{}\n", cycle + 1, world);
-        }
+        assert_answer(17, 2, "day17.txt", Day17::load("day17.txt")?.part2()?);
+        Ok(())
+    }
 
-        println!("Day 17.2: {}", world.count_active());
+    #[test]
+    fn day17_five_dimensions() -> Result<()> {
+        let mut world = NWorld::<5>::load("day17_smoke.txt")?;
+        world.run(6);
+        assert_eq!(5760, world.count_active());
+        Ok(())
+    }
+
+    #[test]
+    fn day17_six_dimensions() -> Result<()> {
+        let mut world = NWorld::<6>::load("day17_smoke.txt")?;
+        // All six cycles reach 35936, but take minutes in a debug build.
+        world.run(2);
+        assert_eq!(464, world.count_active());
+        Ok(())
+    }
+
+    #[test]
+    fn day17_isolated_cube() -> Result<()> {
+        let mut world = NWorld::<3>::parse("#")?.with_rule("B3/S0".parse()?);
+        world.run(3);
+        assert_eq!(1, world.count_active());
+
+        let mut world = NWorld::<3>::parse("#")?;
+        world.step();
+        assert_eq!(0, world.count_active());
+        Ok(())
+    }
+
+    #[test]
+    fn day17_highlife() -> Result<()> {
+        let rule: Rule = "B36/S23".parse()?;
+        assert_eq!(Rule::new(&[3, 6], &[2, 3])?, rule);
+        assert_eq!("B36/S23", rule.to_string());
+
+        let mut world = NWorld::<3>::load("day17_smoke.txt")?.with_rule(rule);
+        world.run(6);
+        assert_eq!(195, world.count_active());
+        Ok(())
+    }
+
+    #[test]
+    fn day17_rules() -> Result<()> {
+        let wide: Rule = "B3,12/S2,3".parse()?;
+        assert_eq!(Rule::new(&[3, 12], &[2, 3])?, wide);
+        assert_eq!("B3,12/S2,3", wide.to_string());
+        assert_eq!(Rule::conway(), "b3/s23".parse()?);
+        assert!("B3S23".parse::<Rule>().is_err());
+        assert!("B3/X23".parse::<Rule>().is_err());
+        assert!("B3/S2x".parse::<Rule>().is_err());
+        assert!("B03/S23".parse::<Rule>().is_err());
+        assert!(Rule::new(&[0], &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day17_glider() -> Result<()> {
+        let mut world = NWorld::<2>::parse(".#.\n..#\n###\n")?;
+        let start = world.active.clone();
+        world.run(4);
+        let moved: HashSet<[i32; 2]> = start.iter().map(|[x, y]| [x + 1, y + 1]).collect();
+        assert_eq!(moved, world.active);
+        assert_eq!(".#.\n..#\n###\n\n", world.to_string());
         Ok(())
     }
 }
@@ -1,237 +1,646 @@
-use std::fmt::Display;
+use std::{collections::HashMap, convert::TryFrom, fmt::Display, iter::Peekable, slice::Iter};
 
-use anyhow::{bail, Context, Result};
-use lazy_static::lazy_static;
-use regex::Regex;
+use anyhow::{anyhow, bail, Context, Result};
 
-#[derive(Debug, Clone)]
+use crate::Solver;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum MathToken {
     Constant(i64),
-    Addition,
-    Multiplication,
+    Variable(String),
+    Operator(char),
     OpenParenthesis,
     CloseParenthesis,
 }
 
-#[derive(Debug, Clone)]
-enum MathExpression {
-    Constant(i64),
-    Operation(MathToken),
-    Parens(MathFormula),
-}
-
 impl Display for MathToken {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             MathToken::Constant(val) => write!(f, "{}", val),
-            MathToken::Addition => write!(f, " + "),
-            MathToken::Multiplication => write!(f, " * "),
+            MathToken::Variable(name) => write!(f, "{}", name),
+            MathToken::Operator(symbol) => write!(f, "{}", symbol),
             MathToken::OpenParenthesis => write!(f, "("),
             MathToken::CloseParenthesis => write!(f, ")"),
         }
     }
 }
 
-impl Display for MathExpression {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            MathExpression::Constant(val) => write!(f, "{}", val),
-            MathExpression::Operation(op) => write!(f, "{}", op),
-            MathExpression::Parens(formula) => write!(f, "({})", formula),
+const OPERATORS: &str = "+-*/%^";
+
+/// A token and the column it starts at, counting from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    token: MathToken,
+    column: usize,
+}
+
+/// What went wrong while reading a formula.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseErrorKind {
+    UnexpectedCharacter,
+    NumberTooBig,
+    /// A `(` which is never closed.
+    UnclosedParenthesis,
+    /// A `)` with no `(` before it.
+    UnmatchedParenthesis,
+    /// An operator with nothing after it, at the end of the line or of a group.
+    TrailingOperator,
+    /// `()` with nothing inside.
+    EmptyGroup,
+    /// An operator where an operand should be.
+    MissingOperand,
+    /// An operand where an operator should be.
+    MissingOperator,
+    UnknownOperator,
+    Empty,
+}
+
+/// A formula which can't be parsed, with the offending token and the column it starts at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    kind: ParseErrorKind,
+    column: usize,
+    token: String,
+}
+
+impl ParseError {
+    fn new(kind: ParseErrorKind, column: usize, token: impl Display) -> ParseError {
+        ParseError {
+            kind,
+            column,
+            token: token.to_string(),
         }
     }
+
+    fn at(kind: ParseErrorKind, token: &Token) -> ParseError {
+        ParseError::new(kind, token.column, &token.token)
+    }
+
+    /// `line` with the error underneath, carets marking the offending token.
+    fn render(&self, line: &str) -> String {
+        let indent = self.column.saturating_sub(1);
+        let width = self.token.chars().count().max(1);
+        format!(
+            "{}\n{}{} {}",
+            line,
+            " ".repeat(indent),
+            "^".repeat(width),
+            self.description()
+        )
+    }
+
+    fn description(&self) -> String {
+        match self.kind {
+            ParseErrorKind::UnexpectedCharacter => format!("Unexpected character '{}'", self.token),
+            ParseErrorKind::NumberTooBig => format!("{} is too big", self.token),
+            ParseErrorKind::UnclosedParenthesis => "Missing ) for this (".to_owned(),
+            ParseErrorKind::UnmatchedParenthesis => "Missing ( for this )".to_owned(),
+            ParseErrorKind::TrailingOperator => format!("Missing operand after {}", self.token),
+            ParseErrorKind::EmptyGroup => "Empty parentheses".to_owned(),
+            ParseErrorKind::MissingOperand => format!("Missing operand before {}", self.token),
+            ParseErrorKind::MissingOperator => format!("Missing operator before {}", self.token),
+            ParseErrorKind::UnknownOperator => format!("Unknown operator {}", self.token),
+            ParseErrorKind::Empty => "Empty formula".to_owned(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at column {}", self.description(), self.column)
+    }
 }
 
+impl std::error::Error for ParseError {}
+
 impl MathToken {
-    fn parse_line(line: &str) -> Result<Vec<MathToken>> {
-        lazy_static! {
-            static ref TOKEN_RE: Regex = Regex::new(r"((?:\d+)|[+*()])").unwrap();
-        }
+    fn parse_line(line: &str) -> Result<Vec<Token>, ParseError> {
         let mut result = vec![];
-
-        for token in TOKEN_RE.captures_iter(line) {
-            let next_token = token.get(0).unwrap().as_str();
-            let next_token = match next_token {
-                "+" => MathToken::Addition,
-                "*" => MathToken::Multiplication,
-                "(" => MathToken::OpenParenthesis,
-                ")" => MathToken::CloseParenthesis,
-                _ => MathToken::Constant(next_token.parse()?),
+        let mut chars = line.chars().enumerate().peekable();
+        while let Some((idx, c)) = chars.next() {
+            let column = idx + 1;
+            let mut word = |first: char, f: fn(&char) -> bool| {
+                let mut text = first.to_string();
+                while let Some((_, c)) = chars.next_if(|(_, c)| f(c)) {
+                    text.push(c);
+                }
+                text
             };
-            result.push(next_token);
+            let token = match c {
+                '(' => MathToken::OpenParenthesis,
+                ')' => MathToken::CloseParenthesis,
+                c if c.is_whitespace() => continue,
+                c if OPERATORS.contains(c) => MathToken::Operator(c),
+                c if c.is_ascii_digit() => {
+                    let digits = word(c, char::is_ascii_digit);
+                    MathToken::Constant(digits.parse().map_err(|_| {
+                        ParseError::new(ParseErrorKind::NumberTooBig, column, &digits)
+                    })?)
+                }
+                c if c.is_alphabetic() || c == '_' => {
+                    MathToken::Variable(word(c, |c| c.is_alphanumeric() || *c == '_'))
+                }
+                c => {
+                    return Err(ParseError::new(
+                        ParseErrorKind::UnexpectedCharacter,
+                        column,
+                        c,
+                    ))
+                }
+            };
+            result.push(Token { token, column });
         }
 
         Ok(result)
     }
 }
 
-impl MathExpression {
-    fn evaluate(&self) -> Result<i64> {
-        // println!("Evaluating self: {:?}",  self);
-        match self {
-            MathExpression::Constant(val) => Ok(*val),
-            MathExpression::Parens(paren_value) => paren_value.evaluate(),
-            _ => bail!("Unexpected expression"),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Associativity {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Operator {
+    symbol: char,
+    precedence: u8,
+    associativity: Associativity,
+    apply: fn(i64, i64) -> Result<i64>,
+}
+
+impl Operator {
+    fn new(symbol: char, precedence: u8, apply: fn(i64, i64) -> Result<i64>) -> Operator {
+        Operator {
+            symbol,
+            precedence,
+            associativity: Associativity::Left,
+            apply,
         }
     }
 
-    fn apply_precedence(&self) -> Result<MathExpression> {
-        if let MathExpression::Parens(inner_formula) = self {
-            if inner_formula.expressions.len() == 1 {
-                Ok(MathExpression::Parens(inner_formula.to_owned()))
-            } else {
-                Ok(MathExpression::Parens(inner_formula.apply_precedence()?))
-            }
-        } else {
-            Ok(self.to_owned())
+    fn right(mut self) -> Operator {
+        self.associativity = Associativity::Right;
+        self
+    }
+}
+
+fn add(a: i64, b: i64) -> Result<i64> {
+    a.checked_add(b)
+        .with_context(|| format!("{} + {} overflows", a, b))
+}
+
+fn subtract(a: i64, b: i64) -> Result<i64> {
+    a.checked_sub(b)
+        .with_context(|| format!("{} - {} overflows", a, b))
+}
+
+fn multiply(a: i64, b: i64) -> Result<i64> {
+    a.checked_mul(b)
+        .with_context(|| format!("{} * {} overflows", a, b))
+}
+
+fn divide(a: i64, b: i64) -> Result<i64> {
+    if b == 0 {
+        bail!("{} / 0 divides by zero", a);
+    }
+    a.checked_div(b)
+        .with_context(|| format!("{} / {} overflows", a, b))
+}
+
+fn remainder(a: i64, b: i64) -> Result<i64> {
+    if b == 0 {
+        bail!("{} % 0 divides by zero", a);
+    }
+    a.checked_rem(b)
+        .with_context(|| format!("{} % {} overflows", a, b))
+}
+
+fn power(a: i64, b: i64) -> Result<i64> {
+    if b < 0 {
+        bail!("{} ^ {} has a negative exponent", a, b);
+    }
+    u32::try_from(b)
+        .ok()
+        .and_then(|b| a.checked_pow(b))
+        .with_context(|| format!("{} ^ {} overflows", a, b))
+}
+
+/// The operators an expression may use. Higher precedence binds tighter.
+#[derive(Debug, Clone)]
+struct OperatorTable {
+    operators: Vec<Operator>,
+    /// How tightly unary minus binds what follows it, if it's allowed at all.
+    negation: Option<u8>,
+}
+
+impl OperatorTable {
+    fn new(operators: Vec<Operator>) -> OperatorTable {
+        OperatorTable {
+            operators,
+            negation: None,
         }
     }
+
+    fn with_negation(mut self, precedence: u8) -> OperatorTable {
+        self.negation = Some(precedence);
+        self
+    }
+
+    /// Part 1: everything but `^` evaluates left to right.
+    fn same_precedence() -> OperatorTable {
+        OperatorTable::new(vec![
+            Operator::new('+', 1, add),
+            Operator::new('-', 1, subtract),
+            Operator::new('*', 1, multiply),
+            Operator::new('/', 1, divide),
+            Operator::new('%', 1, remainder),
+            Operator::new('^', 2, power).right(),
+        ])
+        .with_negation(2)
+    }
+
+    /// Part 2: `+` and `-` before `*`, `/` and `%`.
+    fn addition_first() -> OperatorTable {
+        OperatorTable::new(vec![
+            Operator::new('+', 2, add),
+            Operator::new('-', 2, subtract),
+            Operator::new('*', 1, multiply),
+            Operator::new('/', 1, divide),
+            Operator::new('%', 1, remainder),
+            Operator::new('^', 3, power).right(),
+        ])
+        .with_negation(3)
+    }
+
+    /// What school taught: `^`, then `*`, `/` and `%`, then `+` and `-`. `-2 ^ 2` is -4.
+    fn standard() -> OperatorTable {
+        OperatorTable::new(vec![
+            Operator::new('+', 1, add),
+            Operator::new('-', 1, subtract),
+            Operator::new('*', 2, multiply),
+            Operator::new('/', 2, divide),
+            Operator::new('%', 2, remainder),
+            Operator::new('^', 3, power).right(),
+        ])
+        .with_negation(3)
+    }
+
+    fn get(&self, symbol: char) -> Option<&Operator> {
+        self.operators.iter().find(|op| op.symbol == symbol)
+    }
 }
 
+/// Expression tree. Columns point back into the line, for errors.
 #[derive(Debug, Clone)]
-struct MathFormula {
-    expressions: Vec<MathExpression>,
+enum Expr {
+    Constant(i64),
+    Variable {
+        name: String,
+        column: usize,
+    },
+    Negate {
+        operand: Box<Expr>,
+        column: usize,
+    },
+    Binary {
+        op: Operator,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+        column: usize,
+    },
 }
 
-impl Display for MathFormula {
+/// Every operation in parentheses, e.g. `((2 * 3) + 4)`.
+impl Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for exp in &self.expressions {
-            write!(f, "{}", exp)?;
+        match self {
+            Expr::Constant(val) => write!(f, "{}", val),
+            Expr::Variable { name, .. } => write!(f, "{}", name),
+            Expr::Negate { operand, .. } => write!(f, "(-{})", operand),
+            Expr::Binary { op, lhs, rhs, .. } => write!(f, "({} {} {})", lhs, op.symbol, rhs),
         }
-        Ok(())
     }
 }
 
-impl MathFormula {
-    fn parse_tokens(tokens: &[MathToken]) -> Result<MathFormula> {
-        let mut iterator = tokens.iter();
-        let expressions = MathFormula::parse_tokens_inner(&mut iterator)?;
-        Ok(MathFormula { expressions })
+impl Expr {
+    fn evaluate(&self, variables: &HashMap<String, i64>) -> Result<i64> {
+        match self {
+            Expr::Constant(val) => Ok(*val),
+            Expr::Variable { name, column } => variables
+                .get(name)
+                .copied()
+                .with_context(|| format!("Unknown variable {} at column {}", name, column)),
+            Expr::Negate { operand, column } => {
+                let value = operand.evaluate(variables)?;
+                value
+                    .checked_neg()
+                    .with_context(|| format!("-{} at column {} overflows", value, column))
+            }
+            Expr::Binary {
+                op,
+                lhs,
+                rhs,
+                column,
+            } => {
+                let lhs = lhs.evaluate(variables)?;
+                let rhs = rhs.evaluate(variables)?;
+                (op.apply)(lhs, rhs).with_context(|| format!("At column {}", column))
+            }
+        }
     }
 
-    fn parse_tokens_inner<'a, I>(tokens: &mut I) -> Result<Vec<MathExpression>>
-    where
-        I: Iterator<Item = &'a MathToken>,
-    {
-        let mut result = vec![];
-        while let Some(token) = tokens.next() {
-            let expression = match token {
-                MathToken::Addition => MathExpression::Operation(MathToken::Addition),
-                MathToken::Multiplication => MathExpression::Operation(MathToken::Multiplication),
-                MathToken::Constant(val) => MathExpression::Constant(*val),
-                MathToken::OpenParenthesis => MathExpression::Parens(MathFormula {
-                    expressions: MathFormula::parse_tokens_inner(tokens)?,
-                }),
-                MathToken::CloseParenthesis => break,
-            };
-            result.push(expression);
+    /// Replaces the first operation or variable, in evaluation order, whose operands are known
+    /// with its value. False once there's nothing left to do.
+    fn reduce(&mut self, variables: &HashMap<String, i64>) -> Result<bool> {
+        let reduced_inside = match self {
+            Expr::Constant(_) => return Ok(false),
+            Expr::Variable { .. } => false,
+            Expr::Negate { operand, .. } => operand.reduce(variables)?,
+            Expr::Binary { lhs, rhs, .. } => lhs.reduce(variables)? || rhs.reduce(variables)?,
+        };
+        if !reduced_inside {
+            *self = Expr::Constant(self.evaluate(variables)?);
         }
-
-        Ok(result)
+        Ok(true)
     }
 
-    fn evaluate(&self) -> Result<i64> {
-        let mut accumulator = 0;
-        let mut expressions = self.expressions.iter();
-        while let Some(exp) = expressions.next() {
-            // println!("Accumulator: {}", accumulator);
-            if let MathExpression::Operation(op) = exp {
-                accumulator = match op {
-                    MathToken::Addition => {
-                        accumulator + expressions.next().context("Missing token")?.evaluate()?
-                    }
-                    MathToken::Multiplication => {
-                        accumulator * expressions.next().context("Missing token")?.evaluate()?
+    /// As few parentheses as `table` needs to parse the same tree back.
+    fn render(&self, table: &OperatorTable) -> String {
+        match self {
+            Expr::Constant(val) if *val < 0 => format!("-{}", val.unsigned_abs()),
+            Expr::Constant(val) => val.to_string(),
+            Expr::Variable { name, .. } => name.clone(),
+            Expr::Negate { operand, .. } => {
+                let operand_text = operand.render(table);
+                match operand.binding(table) {
+                    Some(p) if Some(p) < table.negation => format!("-({})", operand_text),
+                    _ => format!("-{}", operand_text),
+                }
+            }
+            Expr::Binary { op, lhs, rhs, .. } => {
+                let mut lhs_text = lhs.render(table);
+                let mut rhs_text = rhs.render(table);
+                let loose = |child: &Expr, same_side: Associativity| match child {
+                    Expr::Binary { op: child_op, .. } => {
+                        child_op.precedence < op.precedence
+                            || (child_op.precedence == op.precedence
+                                && op.associativity != same_side)
                     }
-                    _ => bail!("Invalid token!"),
+                    _ => false,
                 };
-            } else {
-                accumulator = exp.evaluate()?;
+                // A leading minus would grab anything binding as tightly as it does.
+                let negative = matches!(**lhs, Expr::Negate { .. } | Expr::Constant(i64::MIN..=-1));
+                let negated = negative && table.negation.is_some_and(|n| op.precedence >= n);
+                if loose(lhs, Associativity::Left) || negated {
+                    lhs_text = format!("({})", lhs_text);
+                }
+                if loose(rhs, Associativity::Right) {
+                    rhs_text = format!("({})", rhs_text);
+                }
+                format!("{} {} {}", lhs_text, op.symbol, rhs_text)
             }
         }
+    }
 
-        Ok(accumulator)
+    /// Precedence of the outermost operator, if there is one.
+    fn binding(&self, table: &OperatorTable) -> Option<u8> {
+        match self {
+            Expr::Constant(val) if *val < 0 => table.negation,
+            Expr::Constant(_) | Expr::Variable { .. } => None,
+            Expr::Negate { .. } => table.negation,
+            Expr::Binary { op, .. } => Some(op.precedence),
+        }
     }
 
-    fn apply_precedence(&self) -> Result<MathFormula> {
-        let mut current_formula = self.expressions.iter();
-        let mut expressions = vec![];
-
-        let mut prev: Option<MathExpression> = Option::None;
-
-        while let Some(exp) = current_formula.next() {
-            // println!("Parse status({:?}): {:?}", prev, expressions);
-            match exp {
-                MathExpression::Operation(op) => match op {
-                    MathToken::Addition => {
-                        let new_parens = vec![
-                            prev.context("Missing previous")?.apply_precedence()?,
-                            exp.apply_precedence()?,
-                            current_formula
-                                .next()
-                                .context("Missing expression")?
-                                .apply_precedence()?,
-                        ];
-                        prev = Some(MathExpression::Parens(MathFormula {
-                            expressions: new_parens,
-                        }));
-                    }
-                    MathToken::Multiplication => {
-                        expressions.push(prev.context("Missing previous")?);
-                        expressions.push(exp.apply_precedence()?);
-                        prev = Some(
-                            current_formula
-                                .next()
-                                .context("Missing expression")?
-                                .apply_precedence()?,
-                        );
-                    }
-                    _ => bail!("Invalid token"),
-                },
-                _ => {
-                    if let Some(prev_value) = prev {
-                        expressions.push(prev_value);
+    /// The expression after each step of [`Expr::reduce`], starting with this one.
+    fn trace(
+        &self,
+        table: &OperatorTable,
+        variables: &HashMap<String, i64>,
+    ) -> Result<Vec<String>> {
+        let mut expr = self.clone();
+        let mut result = vec![expr.render(table)];
+        while expr.reduce(variables)? {
+            // Negating a constant looks the same before and after.
+            let text = expr.render(table);
+            if result.last() != Some(&text) {
+                result.push(text);
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Pratt parser building an [`Expr`].
+struct Parser<'a> {
+    tokens: Peekable<Iter<'a, Token>>,
+    table: &'a OperatorTable,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(tokens: &[Token], table: &OperatorTable) -> Result<Expr, ParseError> {
+        let mut parser = Parser {
+            tokens: tokens.iter().peekable(),
+            table,
+        };
+        let result = parser.expression(0)?;
+        match parser.tokens.next() {
+            None => Ok(result),
+            Some(token) if token.token == MathToken::CloseParenthesis => {
+                Err(ParseError::at(ParseErrorKind::UnmatchedParenthesis, token))
+            }
+            Some(token) => Err(ParseError::at(ParseErrorKind::MissingOperator, token)),
+        }
+    }
+
+    /// Everything up to the first operator binding looser than `min_precedence`, which is wider
+    /// than a precedence so that it can be one past the tightest.
+    fn expression(&mut self, min_precedence: u16) -> Result<Expr, ParseError> {
+        let mut lhs = self.operand()?;
+        while let Some(token) = self.tokens.peek() {
+            let symbol = match token.token {
+                MathToken::Operator(symbol) => symbol,
+                _ => break,
+            };
+            let op = self
+                .table
+                .get(symbol)
+                .ok_or_else(|| ParseError::at(ParseErrorKind::UnknownOperator, token))?;
+            if u16::from(op.precedence) < min_precedence {
+                break;
+            }
+            let column = token.column;
+            self.operator()?;
+
+            let next_precedence = match op.associativity {
+                Associativity::Left => u16::from(op.precedence) + 1,
+                Associativity::Right => u16::from(op.precedence),
+            };
+            let rhs = self.expression(next_precedence)?;
+            lhs = Expr::Binary {
+                op: *op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+                column,
+            };
+        }
+        Ok(lhs)
+    }
+
+    /// Consumes an operator, making sure something follows it.
+    fn operator(&mut self) -> Result<(), ParseError> {
+        let token = self.tokens.next().expect("peeked an operator");
+        match self.tokens.peek() {
+            None
+            | Some(Token {
+                token: MathToken::CloseParenthesis,
+                ..
+            }) => Err(ParseError::at(ParseErrorKind::TrailingOperator, token)),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Expr, ParseError> {
+        let token = match self.tokens.peek() {
+            Some(token) => *token,
+            // Operators and `(` check there is something after them, so the line is empty.
+            None => return Err(ParseError::new(ParseErrorKind::Empty, 1, "")),
+        };
+        match &token.token {
+            MathToken::Constant(val) => {
+                self.tokens.next();
+                Ok(Expr::Constant(*val))
+            }
+            MathToken::Variable(name) => {
+                self.tokens.next();
+                Ok(Expr::Variable {
+                    name: name.clone(),
+                    column: token.column,
+                })
+            }
+            MathToken::Operator('-') if self.table.negation.is_some() => {
+                self.operator()?;
+                Ok(Expr::Negate {
+                    operand: Box::new(self.expression(self.table.negation.unwrap().into())?),
+                    column: token.column,
+                })
+            }
+            MathToken::OpenParenthesis => {
+                self.tokens.next();
+                match self.tokens.peek() {
+                    None => return Err(ParseError::at(ParseErrorKind::UnclosedParenthesis, token)),
+                    Some(Token {
+                        token: MathToken::CloseParenthesis,
+                        ..
+                    }) => {
+                        return Err(ParseError::new(
+                            ParseErrorKind::EmptyGroup,
+                            token.column,
+                            "()",
+                        ))
                     }
-                    prev = Some(exp.apply_precedence()?);
+                    Some(_) => (),
+                }
+                let result = self.expression(0)?;
+                match self.tokens.next() {
+                    Some(Token {
+                        token: MathToken::CloseParenthesis,
+                        ..
+                    }) => Ok(result),
+                    Some(other) => Err(ParseError::at(ParseErrorKind::MissingOperator, other)),
+                    None => Err(ParseError::at(ParseErrorKind::UnclosedParenthesis, token)),
                 }
             }
+            MathToken::CloseParenthesis => {
+                Err(ParseError::at(ParseErrorKind::UnmatchedParenthesis, token))
+            }
+            MathToken::Operator(_) => Err(ParseError::at(ParseErrorKind::MissingOperand, token)),
         }
+    }
+}
 
-        // println!("Parse status({:?}): {:?}", prev, expressions);
-        if let Some(prev_value) = prev {
-            expressions.push(prev_value);
+fn parse(line: &str, table: &OperatorTable) -> Result<Expr, ParseError> {
+    Parser::parse(&MathToken::parse_line(line)?, table)
+}
+
+fn evaluate(line: &str, table: &OperatorTable) -> Result<i64> {
+    evaluate_with(line, table, &HashMap::new())
+}
+
+/// Like [`evaluate`], with values for the variables in `line`.
+fn evaluate_with(
+    line: &str,
+    table: &OperatorTable,
+    variables: &HashMap<String, i64>,
+) -> Result<i64> {
+    parse(line, table)?.evaluate(variables)
+}
+
+pub struct Day18 {
+    /// Each line with its tokens, kept to point at errors.
+    formulas: Vec<(String, Vec<Token>)>,
+}
+
+impl Day18 {
+    fn sum(&self, table: &OperatorTable) -> Result<i64> {
+        let mut accumulator = 0;
+        for (line, tokens) in &self.formulas {
+            let expr = Parser::parse(tokens, table).map_err(|e| anyhow!("{}", e.render(line)))?;
+            accumulator = expr
+                .evaluate(&HashMap::new())?
+                .checked_add(accumulator)
+                .with_context(|| format!("Sum overflows adding {}", line))?;
         }
-        Ok(MathFormula { expressions })
+        Ok(accumulator)
     }
 }
+
+impl Solver for Day18 {
+    type Part1 = i64;
+    type Part2 = i64;
+
+    fn parse(input: &str) -> Result<Day18> {
+        let mut formulas = vec![];
+        for (idx, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let tokens = MathToken::parse_line(line)
+                .map_err(|e| anyhow!("Line {}:\n{}", idx + 1, e.render(line)))?;
+            formulas.push((line.to_owned(), tokens));
+        }
+        Ok(Day18 { formulas })
+    }
+
+    fn part1(&self) -> Result<i64> {
+        self.sum(&OperatorTable::same_precedence())
+    }
+
+    fn part2(&self) -> Result<i64> {
+        self.sum(&OperatorTable::addition_first())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn day18_smoke1() -> Result<()> {
-        let expression =
-            MathFormula::parse_tokens(&MathToken::parse_line("1 + 2 * 3 + 4 * 5 + 6")?)?;
-        assert_eq!(71, expression.evaluate()?);
-        let expression =
-            MathFormula::parse_tokens(&MathToken::parse_line("1 + (2 * 3) + (4 * (5 + 6))")?)?;
-        assert_eq!(51, expression.evaluate()?);
-        let expression = MathFormula::parse_tokens(&MathToken::parse_line(
-            "((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2",
-        )?)?;
-        assert_eq!(13632, expression.evaluate()?);
+        let table = OperatorTable::same_precedence();
+        assert_eq!(71, evaluate("1 + 2 * 3 + 4 * 5 + 6", &table)?);
+        assert_eq!(51, evaluate("1 + (2 * 3) + (4 * (5 + 6))", &table)?);
+        assert_eq!(
+            13632,
+            evaluate("((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2", &table)?
+        );
         Ok(())
     }
 
     #[test]
     fn day18_1() -> Result<()> {
-        let mut accumulator = 0;
-
-        for line in crate::read_file("day18.txt")? {
-            let line = line?;
-            let expression = MathFormula::parse_tokens(&MathToken::parse_line(&line)?)?;
-            accumulator += expression.evaluate()?;
-        }
+        let accumulator = Day18::load("day18.txt")?.part1()?;
 
         println!("Day 18.1: {}", accumulator);
         assert_eq!(12956356593940, accumulator);
@@ -240,39 +649,254 @@ mod tests {
 
     #[test]
     fn day18_smoke2() -> Result<()> {
-        let expression =
-            MathFormula::parse_tokens(&MathToken::parse_line("1 + 2 * 3 + 4 * 5 + 6")?)?
-                .apply_precedence()?;
-        println!("Day 18 smoke2: {}", expression);
-        assert_eq!(231, expression.evaluate()?);
-        let expression =
-            MathFormula::parse_tokens(&MathToken::parse_line("1 + (2 * 3) + (4 * (5 + 6))")?)?
-                .apply_precedence()?;
-        println!("Day 18 smoke2: {}", expression);
-        assert_eq!(51, expression.evaluate()?);
-        let expression = MathFormula::parse_tokens(&MathToken::parse_line(
-            "((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2",
-        )?)?
-        .apply_precedence()?;
-        println!("Day 18 smoke2: {}", expression);
-        assert_eq!(23340, expression.evaluate()?);
+        let table = OperatorTable::addition_first();
+        assert_eq!(231, evaluate("1 + 2 * 3 + 4 * 5 + 6", &table)?);
+        assert_eq!(51, evaluate("1 + (2 * 3) + (4 * (5 + 6))", &table)?);
+        assert_eq!(
+            23340,
+            evaluate("((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2", &table)?
+        );
 
         Ok(())
     }
 
     #[test]
     fn day18_2() -> Result<()> {
-        let mut accumulator = 0;
-
-        for line in crate::read_file("day18.txt")? {
-            let line = line?;
-            let expression =
-                MathFormula::parse_tokens(&MathToken::parse_line(&line)?)?.apply_precedence()?;
-            accumulator += expression.evaluate()?;
-        }
+        let accumulator = Day18::load("day18.txt")?.part2()?;
 
         println!("Day 18.2: {}", accumulator);
         assert_eq!(94240043727614, accumulator);
         Ok(())
     }
+
+    #[test]
+    fn day18_operator_tables() -> Result<()> {
+        assert_eq!(
+            33,
+            evaluate("1 + 2 * 3 + 4 * 5 + 6", &OperatorTable::standard())?
+        );
+
+        let minus = |associativity| Operator {
+            symbol: '-',
+            precedence: 1,
+            associativity,
+            apply: subtract,
+        };
+        let left = OperatorTable::new(vec![minus(Associativity::Left)]);
+        let right = OperatorTable::new(vec![minus(Associativity::Right)]);
+        assert_eq!(3, evaluate("8 - 3 - 2", &left)?);
+        assert_eq!(7, evaluate("8 - 3 - 2", &right)?);
+
+        let power = OperatorTable::new(vec![
+            Operator::new('*', 1, multiply),
+            Operator::new('^', 2, power).right(),
+        ]);
+        assert_eq!(2 * 512, evaluate("2 * 2 ^ 3 ^ 2", &power)?);
+
+        let tightest = OperatorTable::new(vec![Operator::new('-', u8::MAX, subtract)]);
+        assert_eq!(3, evaluate("8 - 3 - 2", &tightest)?);
+
+        assert!(evaluate("1 - 2", &power).is_err());
+        assert!(evaluate("-2", &power).is_err());
+        assert!(evaluate("(1 + 2", &OperatorTable::standard()).is_err());
+        assert!(evaluate("1 + 2)", &OperatorTable::standard()).is_err());
+        assert!(evaluate("1 +", &OperatorTable::standard()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day18_arithmetic() -> Result<()> {
+        let table = OperatorTable::standard();
+        assert_eq!(1, evaluate("3 - 2", &table)?);
+        assert_eq!(-3, evaluate("10 - 6 - 7", &table)?);
+        assert_eq!(3, evaluate("7 / 2", &table)?);
+        assert_eq!(1, evaluate("7 % 3", &table)?);
+        assert_eq!(-4, evaluate("-2 ^ 2", &table)?);
+        assert_eq!(4, evaluate("(-2) ^ 2", &table)?);
+        assert_eq!(-6, evaluate("-2 * 3", &table)?);
+        assert_eq!(2, evaluate("--2", &table)?);
+        assert_eq!(5, evaluate("2 - -3", &table)?);
+        assert_eq!(-11, evaluate("1 - 3 * 2 ^ 2", &table)?);
+
+        let part1 = OperatorTable::same_precedence();
+        assert_eq!(-4, evaluate("1 - 3 * 2 ^ 1", &part1)?);
+        let part2 = OperatorTable::addition_first();
+        assert_eq!(9, evaluate("2 + 1 * 4 - 1", &part2)?);
+        Ok(())
+    }
+
+    #[test]
+    fn day18_variables() -> Result<()> {
+        let mut variables = HashMap::new();
+        variables.insert("x".to_owned(), 4);
+        variables.insert("rate_2".to_owned(), -3);
+        let table = OperatorTable::standard();
+        assert_eq!(-11, evaluate_with("x * rate_2 + 1", &table, &variables)?);
+        assert_eq!(3, evaluate_with("-rate_2", &table, &variables)?);
+
+        let e = evaluate_with("x + y", &table, &variables).unwrap_err();
+        assert_eq!("Unknown variable y at column 5", e.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn day18_errors() -> Result<()> {
+        let table = OperatorTable::standard();
+        let e = evaluate("3 & 2", &table).unwrap_err();
+        assert_eq!("Unexpected character '&' at column 3", e.to_string());
+
+        let e = evaluate("1 + 9223372036854775807", &table).unwrap_err();
+        assert_eq!(
+            "At column 3: 1 + 9223372036854775807 overflows",
+            format!("{:#}", e)
+        );
+        assert!(evaluate("2 ^ 64", &table).is_err());
+        assert!(evaluate("2 ^ -1", &table).is_err());
+        assert!(evaluate("1 / (2 - 2)", &table).is_err());
+        assert!(evaluate("1 % 0", &table).is_err());
+        assert!(evaluate("99999999999999999999", &table).is_err());
+        assert!(evaluate("-(-9223372036854775807 - 1)", &table).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day18_sum_overflow() -> Result<()> {
+        let day = Day18::parse("9223372036854775807\n1 * 1\n")?;
+        assert_eq!(
+            "Sum overflows adding 1 * 1",
+            day.part1().unwrap_err().to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn day18_parse_errors() {
+        let table = OperatorTable::standard();
+        let error = |line: &str| parse(line, &table).unwrap_err();
+        let cases = [
+            ("3 & 2", ParseErrorKind::UnexpectedCharacter, 3, "&"),
+            (
+                "99999999999999999999",
+                ParseErrorKind::NumberTooBig,
+                1,
+                "99999999999999999999",
+            ),
+            ("1 + (2 * 3", ParseErrorKind::UnclosedParenthesis, 5, "("),
+            ("(", ParseErrorKind::UnclosedParenthesis, 1, "("),
+            ("1 + 2) * 3", ParseErrorKind::UnmatchedParenthesis, 6, ")"),
+            (") + 1", ParseErrorKind::UnmatchedParenthesis, 1, ")"),
+            ("1 +", ParseErrorKind::TrailingOperator, 3, "+"),
+            ("(1 *) + 2", ParseErrorKind::TrailingOperator, 4, "*"),
+            ("2 * -", ParseErrorKind::TrailingOperator, 5, "-"),
+            ("1 + ()", ParseErrorKind::EmptyGroup, 5, "()"),
+            ("1 + * 2", ParseErrorKind::MissingOperand, 5, "*"),
+            ("1 2", ParseErrorKind::MissingOperator, 3, "2"),
+            ("(1 x)", ParseErrorKind::MissingOperator, 4, "x"),
+            ("  ", ParseErrorKind::Empty, 1, ""),
+        ];
+        for (line, kind, column, token) in cases.iter() {
+            assert_eq!(
+                ParseError::new(*kind, *column, token),
+                error(line),
+                "{}",
+                line
+            );
+        }
+
+        let table = OperatorTable::new(vec![Operator::new('+', 1, add)]);
+        assert_eq!(
+            ParseError::new(ParseErrorKind::UnknownOperator, 3, '*'),
+            parse("1 * 2", &table).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn day18_render_errors() {
+        let table = OperatorTable::standard();
+        let line = "1 + (2 * 3";
+        assert_eq!(
+            "1 + (2 * 3\n    ^ Missing ) for this (",
+            parse(line, &table).unwrap_err().render(line)
+        );
+        let line = "12 + 1234567890123456789012";
+        assert_eq!(
+            "12 + 1234567890123456789012\n     ^^^^^^^^^^^^^^^^^^^^^^ 1234567890123456789012 is too big",
+            parse(line, &table).unwrap_err().render(line)
+        );
+        assert_eq!(
+            "Empty parentheses at column 1",
+            parse("()", &table).unwrap_err().to_string()
+        );
+
+        let e = Day18::parse("1 + 2\n3 $ 4\n").err().unwrap();
+        assert_eq!(
+            "Line 2:\n3 $ 4\n  ^ Unexpected character '$'",
+            e.to_string()
+        );
+    }
+
+    #[test]
+    fn day18_trace() -> Result<()> {
+        let table = OperatorTable::addition_first();
+        let expr = parse("2 * 3 + 4", &table)?;
+        assert_eq!("(2 * (3 + 4))", expr.to_string());
+        let trace = expr.trace(&table, &HashMap::new())?;
+        assert_eq!("2 * 3 + 4 → 2 * 7 → 14", trace.join(" → "));
+
+        let table = OperatorTable::same_precedence();
+        let expr = parse("1 + (2 * 3) + (4 * (5 + 6))", &table)?;
+        assert_eq!("((1 + (2 * 3)) + (4 * (5 + 6)))", expr.to_string());
+        assert_eq!(
+            vec![
+                "1 + (2 * 3) + (4 * (5 + 6))",
+                "1 + 6 + (4 * (5 + 6))",
+                "7 + (4 * (5 + 6))",
+                "7 + (4 * 11)",
+                "7 + 44",
+                "51",
+            ],
+            expr.trace(&table, &HashMap::new())?
+        );
+
+        let mut variables = HashMap::new();
+        variables.insert("x".to_owned(), 2);
+        let table = OperatorTable::standard();
+        let expr = parse("-x ^ 2 - (1 - 3)", &table)?;
+        assert_eq!("((-(x ^ 2)) - (1 - 3))", expr.to_string());
+        assert_eq!(
+            "-x ^ 2 - (1 - 3) → -2 ^ 2 - (1 - 3) → -4 - (1 - 3) → -4 - -2 → -2",
+            expr.trace(&table, &variables)?.join(" → ")
+        );
+        assert_eq!(
+            "(-2) ^ 2 → 4",
+            parse("(-2) ^ 2", &table)?
+                .trace(&table, &variables)?
+                .join(" → ")
+        );
+        assert!(parse("x / 0", &table)?.trace(&table, &variables).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn day18_render_round_trip() -> Result<()> {
+        let lines = [
+            "1 + 2 * 3 + 4 * 5 + 6",
+            "((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2",
+            "2 ^ 3 ^ 2 - (2 ^ 3) ^ 2",
+            "-(1 + 2) * -3 - -(-4)",
+            "10 - (6 - 7) / (2 % 3)",
+        ];
+        for table in &[
+            OperatorTable::same_precedence(),
+            OperatorTable::addition_first(),
+            OperatorTable::standard(),
+        ] {
+            for line in &lines {
+                let expr = parse(line, table)?;
+                let again = parse(&expr.render(table), table)?;
+                assert_eq!(expr.to_string(), again.to_string(), "{}", line);
+            }
+        }
+        Ok(())
+    }
 }
@@ -1,184 +1,1202 @@
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{btree_set, BTreeSet, HashMap, HashSet},
+    fmt::Display,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use lazy_static::lazy_static;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use regex::Regex;
 
-fn load_day19_file(file_name: &str) -> Result<(HashMap<u32, String>, Vec<String>)> {
-    lazy_static! {
-        static ref RULE_RE: Regex = Regex::new(r"^(\d+): (.*)$").unwrap();
-    }
-    let mut raw_lines = HashMap::new();
-    let mut messages = vec![];
-
-    for line in crate::read_file(file_name)? {
-        let line = line?;
-        let line = line.trim();
-
-        if line.is_empty() {
-            continue;
-        } else if let Some(captures) = RULE_RE.captures(line) {
-            let rule_num = captures
-                .get(1)
-                .context("Missing number")?
-                .as_str()
-                .parse()?;
-            let content = captures.get(2).context("Missing content")?.as_str();
-            raw_lines.insert(rule_num, content.to_owned());
-        } else {
-            messages.push(line.to_owned());
-        }
-    }
+use crate::Solver;
 
-    Ok((raw_lines, messages))
-}
-
-fn build_single_rule(rule_num: u32, raw: &HashMap<u32, String>) -> Result<Regex> {
-    let mut pre_parsed = HashMap::new();
-    let mut result = String::new();
-    result += "^";
-    result += &build_message_rule(rule_num, raw, &mut pre_parsed)?;
-    result += "$";
-
-    Regex::new(&result).context("Invalid regex")
-}
+/// Characters one position of a message may hold, as inclusive ranges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CharClass(Vec<(char, char)>);
 
-// TODO: Use lifetimes to avoid extra copies
-fn build_message_rule(
-    rule_num: u32,
-    raw: &HashMap<u32, String>,
-    pre_parsed: &mut HashMap<u32, String>,
-) -> Result<String> {
-    lazy_static! {
-        // static ref RULE_RE : Regex = Regex::new(r"^(\d+): (.*)$").unwrap();
-        static ref LITERAL_RE : Regex = Regex::new("\"(.)\"").unwrap();
+impl CharClass {
+    fn single(c: char) -> CharClass {
+        CharClass(vec![(c, c)])
     }
 
-    if let Some(result) = pre_parsed.get(&rule_num) {
-        return Ok(result.to_owned());
+    fn contains(&self, c: char) -> bool {
+        self.0.iter().any(|(from, to)| (*from..=*to).contains(&c))
     }
 
-    let contents = raw.get(&rule_num).context("Missing raw rule")?;
+    fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.0.iter().flat_map(|(from, to)| *from..=*to)
+    }
 
-    if let Some(literal_captures) = LITERAL_RE.captures(contents) {
-        let result = literal_captures.get(1).context("Missing literal")?.as_str();
-        pre_parsed.insert(rule_num, result.to_owned());
-        Ok(result.to_owned())
-    } else {
-        let mut result = "(?:".to_owned();
-        for element in contents.split(' ') {
-            match element {
-                "|" => result += element,
-                _ => result += &build_message_rule(element.parse()?, raw, pre_parsed)?,
-            };
+    fn to_regex(&self) -> String {
+        match self.0.as_slice() {
+            [(from, to)] if from == to => regex::escape(&from.to_string()),
+            ranges => {
+                let mut result = "[".to_owned();
+                for (from, to) in ranges {
+                    result += &regex::escape(&from.to_string());
+                    if from != to {
+                        result.push('-');
+                        result += &regex::escape(&to.to_string());
+                    }
+                }
+                result.push(']');
+                result
+            }
         }
-        result += ")";
-        Ok(result)
     }
 }
 
 #[derive(Debug, Clone)]
 enum RuleOutput {
-    Terminal(char),
+    Terminal(CharClass),
     Nonterminal(Vec<Vec<u32>>),
 }
 
+/// Rules by id. Numbered rules keep their number, named ones get the ids after
+/// the highest number, and literals, classes and repetitions inside rules become
+/// anonymous rules after those.
 #[derive(Debug, Clone)]
-struct RuleTable(HashMap<u32, RuleOutput>);
+struct RuleTable {
+    rules: HashMap<u32, RuleOutput>,
+    names: HashMap<u32, String>,
+}
 
 impl RuleTable {
     fn parse_file(file_name: &str) -> Result<(RuleTable, Vec<String>)> {
+        RuleTable::parse(&crate::read_input(file_name)?)
+    }
+
+    /// Rules look like `name: a "lit" [a-z]+ | b c?`, anything else without spaces is a message.
+    fn parse(input: &str) -> Result<(RuleTable, Vec<String>)> {
         lazy_static! {
-            static ref RULE_RE: Regex = Regex::new(r"^(\d+): (.*)$").unwrap();
-            static ref LITERAL_RE: Regex = Regex::new("\"(.)\"").unwrap();
+            static ref RULE_RE: Regex = Regex::new(r"^(\w+):(\s.*|)$").unwrap();
+            static ref MESSAGE_RE: Regex = Regex::new(r"^\S+$").unwrap();
         }
         let mut messages = vec![];
-        let mut map: HashMap<u32, RuleOutput> = HashMap::new();
+        let mut definitions = vec![];
 
-        for line in crate::read_file(file_name)? {
-            let line = line?;
+        for (idx, line) in input.lines().enumerate() {
             let line = line.trim();
 
             if line.is_empty() {
                 continue;
             } else if let Some(captures) = RULE_RE.captures(line) {
-                let rule_num = captures
-                    .get(1)
-                    .context("Missing number")?
-                    .as_str()
-                    .parse()?;
+                let name = captures.get(1).context("Missing name")?.as_str();
                 let content = captures.get(2).context("Missing content")?.as_str();
+                definitions.push((idx + 1, line, name, content.trim()));
+            } else if MESSAGE_RE.is_match(line) {
+                messages.push(line.to_owned());
+            } else {
+                bail!("Line {} is neither a rule nor a message: {}", idx + 1, line);
+            }
+        }
 
-                if let Some(l) = LITERAL_RE.captures(content) {
-                    let c = l.get(1).unwrap().as_str().chars().next().unwrap();
-                    map.insert(rule_num, RuleOutput::Terminal(c));
-                } else {
-                    let mut output_choices = vec![];
-                    let mut working_target: Vec<u32> = vec![];
-
-                    for token in content.split(' ') {
-                        if token == "|" {
-                            output_choices.push(working_target);
-                            working_target = vec![];
-                        } else {
-                            working_target.push(token.parse()?);
+        let mut ids = HashMap::new();
+        let numbered = definitions
+            .iter()
+            .filter_map(|(_, _, name, _)| name.parse::<u32>().ok());
+        let mut next_id = numbered.max().map_or(0, |max| max + 1);
+        for (line_num, line, name, _) in &definitions {
+            let id = match name.parse::<u32>() {
+                Ok(id) => id,
+                Err(_) if name.chars().all(|c| c.is_ascii_digit()) => {
+                    bail!("Line {}: rule number {} is too big", line_num, name)
+                }
+                Err(_) => {
+                    next_id += 1;
+                    next_id - 1
+                }
+            };
+            if ids.insert(name.to_string(), id).is_some() {
+                bail!(
+                    "Line {}: rule {} is defined twice: {}",
+                    line_num,
+                    name,
+                    line
+                );
+            }
+        }
+
+        let mut builder = RuleBuilder {
+            rules: HashMap::new(),
+            ids,
+            next_id,
+        };
+        for (line_num, line, name, content) in &definitions {
+            let output = builder
+                .body(content)
+                .with_context(|| format!("Line {}: {}", line_num, line))?;
+            let id = builder.ids[*name];
+            builder.rules.insert(id, output);
+        }
+
+        let names = builder
+            .ids
+            .into_iter()
+            .map(|(name, id)| (id, name))
+            .collect();
+        Ok((
+            RuleTable {
+                rules: builder.rules,
+                names,
+            },
+            messages,
+        ))
+    }
+
+    /// Id of the rule called `name`.
+    fn rule(&self, name: &str) -> Result<u32> {
+        self.names
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(id, _)| *id)
+            .with_context(|| format!("No rule called {}", name))
+    }
+
+    /// Rules named in any alternative of `rule_num`.
+    fn children(&self, rule_num: u32) -> Vec<u32> {
+        match self.rules.get(&rule_num) {
+            Some(RuleOutput::Nonterminal(choices)) => choices.iter().flatten().copied().collect(),
+            _ => vec![],
+        }
+    }
+
+    /// For each rule, every rule reachable from it in one step or more.
+    fn reaches(&self) -> HashMap<u32, HashSet<u32>> {
+        let mut result = HashMap::new();
+        for rule in self.rules.keys() {
+            let mut seen = HashSet::new();
+            let mut todo = self.children(*rule);
+            while let Some(next) = todo.pop() {
+                if seen.insert(next) {
+                    todo.extend(self.children(next));
+                }
+            }
+            result.insert(*rule, seen);
+        }
+        result
+    }
+
+    /// Shortest match of each rule, missing for rules which can't match anything.
+    fn min_lengths(&self) -> HashMap<u32, usize> {
+        let mut min_len: HashMap<u32, usize> = HashMap::new();
+        loop {
+            let mut changed = false;
+            for (rule, output) in &self.rules {
+                let len = match output {
+                    RuleOutput::Terminal(_) => Some(1),
+                    RuleOutput::Nonterminal(choices) => choices
+                        .iter()
+                        .filter_map(|choice| choice.iter().map(|r| min_len.get(r)).sum())
+                        .min(),
+                };
+                if let Some(len) = len {
+                    if min_len.get(rule).is_none_or(|old| len < *old) {
+                        min_len.insert(*rule, len);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                return min_len;
+            }
+        }
+    }
+
+    fn name(&self, rule_num: u32) -> String {
+        match self.names.get(&rule_num) {
+            Some(name) => name.clone(),
+            None => rule_num.to_string(),
+        }
+    }
+
+    fn alternatives(&self, rule_num: u32) -> usize {
+        match self.rules.get(&rule_num) {
+            None => 0,
+            Some(RuleOutput::Terminal(_)) => 1,
+            Some(RuleOutput::Nonterminal(choices)) => choices.len(),
+        }
+    }
+
+    /// The symbol right after the dot, or None if `item` is complete.
+    fn symbol(&self, item: &Item) -> Option<Symbol<'_>> {
+        match self.rules.get(&item.rule)? {
+            RuleOutput::Terminal(class) if item.dot == 0 => Some(Symbol::Class(class)),
+            RuleOutput::Terminal(_) => None,
+            RuleOutput::Nonterminal(choices) => {
+                choices[item.alt].get(item.dot).map(|r| Symbol::Rule(*r))
+            }
+        }
+    }
+
+    /// Earley chart for `s`: one set of items per position, including the end.
+    fn earley(&self, rule_num: u32, s: &str) -> Chart {
+        let chars: Vec<char> = s.chars().collect();
+        let mut chart = Chart::new(chars.len() + 1);
+        for alt in 0..self.alternatives(rule_num) {
+            chart.add(0, Item::start(rule_num, alt, 0), None, None);
+        }
+
+        for i in 0..=chars.len() {
+            // Rules which matched nothing at `i`, for items predicting them after they completed.
+            let mut empty: HashMap<u32, usize> = HashMap::new();
+            let mut idx = 0;
+            while idx < chart.sets[i].len() {
+                let item = chart.sets[i][idx].item;
+                match self.symbol(&item) {
+                    Some(Symbol::Class(class)) => {
+                        if let Some(c) = chars.get(i).filter(|c| class.contains(**c)) {
+                            let child = Some(Child::Char(*c));
+                            chart.add(i + 1, item.advance(), Some((i, idx)), child);
                         }
                     }
-                    if !working_target.is_empty() {
-                        output_choices.push(working_target);
+                    Some(Symbol::Rule(next)) => {
+                        for alt in 0..self.alternatives(next) {
+                            chart.add(i, Item::start(next, alt, i), None, None);
+                        }
+                        if let Some(done) = empty.get(&next) {
+                            let child = Child::Rule(i, *done);
+                            chart.add(i, item.advance(), Some((i, idx)), Some(child));
+                        }
+                    }
+                    None => {
+                        if item.origin == i {
+                            empty.entry(item.rule).or_insert(idx);
+                        }
+                        let mut waiting = 0;
+                        while waiting < chart.sets[item.origin].len() {
+                            let parent = chart.sets[item.origin][waiting].item;
+                            if self.symbol(&parent) == Some(Symbol::Rule(item.rule)) {
+                                let prev = Some((item.origin, waiting));
+                                let child = Child::Rule(i, idx);
+                                chart.add(i, parent.advance(), prev, Some(child));
+                            }
+                            waiting += 1;
+                        }
                     }
-                    map.insert(rule_num, RuleOutput::Nonterminal(output_choices));
                 }
-            } else {
-                messages.push(line.to_owned());
+                idx += 1;
             }
         }
+        chart
+    }
 
-        Ok((RuleTable(map), messages))
+    /// How `rule_num` matches all of `s`. For an ambiguous grammar this is one of the ways.
+    fn parse_tree(&self, rule_num: u32, s: &str) -> Option<ParseTree> {
+        let chart = self.earley(rule_num, s);
+        let end = chart.sets.len() - 1;
+        let idx = chart.sets[end].iter().position(|entry| {
+            entry.item.rule == rule_num
+                && entry.item.origin == 0
+                && self.symbol(&entry.item).is_none()
+        })?;
+        Some(ParseTree::Node {
+            rule: self.name(rule_num),
+            children: chart.children(self, end, idx),
+        })
     }
 
     fn is_match(&self, rule_num: u32, s: &str) -> bool {
-        let chars: Vec<char> = s.chars().collect();
-        let RuleTable(rules) = self;
-
-        // let mut position = 0
-        let mut possibilities = VecDeque::new();
-        possibilities.push_back((0 as usize, vec![rules.get(&rule_num).unwrap()]));
-
-        while !possibilities.is_empty() {
-            let mut state = possibilities.pop_front().unwrap();
-            // println!("\t {:?}", state);
-            if state.0 == s.len() && state.1.is_empty() {
-                // if !possibilities.is_empty() {
-                //     println!("\tAccepted with {} possibilities left.", possibilities.len());
-                // }
-                return true;
-            } else if state.0 == s.len() || state.1.is_empty() {
-                continue;
+        self.parse_tree(rule_num, s).is_some()
+    }
+
+    /// Compiles `rule_num` into a regex. Recursion a regex can't express is an
+    /// error, unless `max_len` is given to unroll it for messages up to that long.
+    fn compile_regex(&self, rule_num: u32, max_len: Option<usize>) -> Result<RuleRegex> {
+        let mut compiler = RegexCompiler::new(self);
+        if max_len.is_none() {
+            let mut rules = vec![rule_num];
+            rules.extend(compiler.reaches.get(&rule_num).into_iter().flatten());
+            rules.sort_unstable();
+            if let Some(rule) = rules
+                .iter()
+                .find(|r| compiler.recursion(**r) == Recursion::Irregular)
+            {
+                bail!("Rule {} is not regular", rule);
+            }
+        }
+        let source = compiler
+            .rule(rule_num, max_len)?
+            .unwrap_or_else(|| "[^\\s\\S]".to_owned());
+        let regex = Regex::new(&format!("^{}$", source)).context("Invalid regex")?;
+        let max_len = max_len.filter(|_| compiler.bounded.contains(&rule_num));
+        Ok(RuleRegex { regex, max_len })
+    }
+
+    /// Picks a regex if `rule_num` compiles to one for the longest of `messages`, or the parser.
+    fn matcher(&self, rule_num: u32, messages: &[String]) -> Matcher<'_> {
+        let max_len = messages
+            .iter()
+            .map(|m| m.chars().count())
+            .max()
+            .unwrap_or(0);
+        Matcher {
+            rules: self,
+            rule_num,
+            regex: self.compile_regex(rule_num, Some(max_len)).ok(),
+        }
+    }
+
+    /// How many of `messages` rule `rule_num` matches.
+    fn count_matches(&self, rule_num: u32, messages: &[String]) -> usize {
+        let matcher = self.matcher(rule_num, messages);
+        messages.iter().filter(|m| matcher.is_match(m)).count()
+    }
+}
+
+/// Turns rule bodies into [`RuleOutput`]s, adding anonymous rules as it goes.
+struct RuleBuilder {
+    rules: HashMap<u32, RuleOutput>,
+    ids: HashMap<String, u32>,
+    next_id: u32,
+}
+
+impl RuleBuilder {
+    fn anonymous(&mut self, output: RuleOutput) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.rules.insert(id, output);
+        id
+    }
+
+    fn body(&mut self, content: &str) -> Result<RuleOutput> {
+        let mut chars = content.chars().peekable();
+        let mut choices = vec![];
+        // Each item is the rules it stands for, so repetition can wrap all of them.
+        let mut items: Vec<Vec<u32>> = vec![];
+        let mut finish = |items: &mut Vec<Vec<u32>>| -> Result<()> {
+            if items.is_empty() {
+                bail!("Empty alternative, use \"\" to match nothing");
             }
+            choices.push(items.drain(..).flatten().collect::<Vec<u32>>());
+            Ok(())
+        };
 
-            let next_symbol = state.1.pop().unwrap();
-            match next_symbol {
-                RuleOutput::Terminal(c) => {
-                    if *c == chars[state.0] {
-                        state.0 += 1;
-                        possibilities.push_back(state);
+        while let Some(c) = chars.next() {
+            match c {
+                c if c.is_whitespace() => (),
+                '|' => finish(&mut items)?,
+                '"' => {
+                    let mut literal = vec![];
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => {
+                                literal.push(chars.next().context("Unterminated literal")?)
+                            }
+                            Some(c) => literal.push(c),
+                            None => bail!("Unterminated literal"),
+                        }
                     }
+                    let item = literal
+                        .into_iter()
+                        .map(|c| self.anonymous(RuleOutput::Terminal(CharClass::single(c))))
+                        .collect();
+                    items.push(item);
                 }
-                RuleOutput::Nonterminal(vv) => {
-                    for choice in vv {
-                        let mut new_state = state.clone();
-                        for s in choice.iter().rev() {
-                            let rule_output = rules.get(s).unwrap();
-                            new_state.1.push(rule_output);
+                '[' => {
+                    let mut ranges = vec![];
+                    loop {
+                        let from = match chars.next() {
+                            Some(']') => break,
+                            Some('\\') => chars.next().context("Unterminated class")?,
+                            Some(c) => c,
+                            None => bail!("Unterminated class"),
+                        };
+                        let to = match chars.next_if_eq(&'-') {
+                            Some(_) => match chars.next() {
+                                Some('\\') => chars.next().context("Unterminated class")?,
+                                Some(']') | None => bail!("Unfinished range from {:?}", from),
+                                Some(c) => c,
+                            },
+                            None => from,
+                        };
+                        if to < from {
+                            bail!("Range {:?}-{:?} is backwards", from, to);
                         }
-                        possibilities.push_back(new_state);
+                        ranges.push((from, to));
                     }
+                    if ranges.is_empty() {
+                        bail!("Empty class []");
+                    }
+                    items.push(vec![self.anonymous(RuleOutput::Terminal(CharClass(ranges)))]);
+                }
+                '*' | '+' | '?' => {
+                    let item = items
+                        .pop()
+                        .with_context(|| format!("Nothing to repeat before {}", c))?;
+                    let id = self.next_id;
+                    let again: Vec<u32> = item.iter().copied().chain(Some(id)).collect();
+                    let choices = match c {
+                        '*' => vec![again, vec![]],
+                        '+' => vec![again, item],
+                        _ => vec![item, vec![]],
+                    };
+                    items.push(vec![self.anonymous(RuleOutput::Nonterminal(choices))]);
                 }
+                c if c.is_alphanumeric() || c == '_' => {
+                    let mut name = c.to_string();
+                    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                        name.push(c);
+                    }
+                    let id = self
+                        .ids
+                        .get(&name)
+                        .with_context(|| format!("Unknown rule {}", name))?;
+                    items.push(vec![*id]);
+                }
+                c => bail!("Unexpected {:?}", c),
+            }
+        }
+        finish(&mut items)?;
+
+        // A rule which is just one character, like `4: "a"`, needs no anonymous rule.
+        if let [choice] = choices.as_slice() {
+            if let [id] = choice.as_slice() {
+                let named = self.ids.values().any(|v| v == id);
+                if let (false, Some(RuleOutput::Terminal(_))) = (named, self.rules.get(id)) {
+                    return Ok(self.rules.remove(id).unwrap());
+                }
+            }
+        }
+        Ok(RuleOutput::Nonterminal(choices))
+    }
+}
+
+/// Longest regex pattern [`RegexCompiler`] builds before giving up.
+const MAX_PATTERN_LEN: usize = 1 << 18;
+
+/// A regex for a rule. If the grammar had to be unrolled, it is only right for
+/// messages up to `max_len` characters.
+#[derive(Debug, Clone)]
+struct RuleRegex {
+    regex: Regex,
+    max_len: Option<usize>,
+}
+
+impl RuleRegex {
+    /// None if `s` is too long for the regex to tell.
+    fn is_match(&self, s: &str) -> Option<bool> {
+        match self.max_len {
+            Some(max_len) if s.chars().count() > max_len => None,
+            _ => Some(self.regex.is_match(s)),
+        }
+    }
+}
+
+/// How a rule refers back to itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Recursion {
+    None,
+    /// Only directly, and only at the start or end of an alternative, so `*` covers it.
+    Regular,
+    /// Anything else, which gets unrolled.
+    Irregular,
+}
+
+/// Turns rules into regex source.
+///
+/// Irregular rules are expanded with a budget: the most characters they may
+/// match, given the least their neighbours need. Every loop through them uses
+/// some of it up, so the expansion ends.
+struct RegexCompiler<'a> {
+    rules: &'a HashMap<u32, RuleOutput>,
+    reaches: HashMap<u32, HashSet<u32>>,
+    min_len: HashMap<u32, usize>,
+    /// Rules with an irregular rule somewhere under them, whose regex depends on the budget.
+    bounded: HashSet<u32>,
+    memo: HashMap<(u32, Option<usize>), Option<String>>,
+    stack: Vec<(u32, Option<usize>)>,
+}
+
+impl<'a> RegexCompiler<'a> {
+    fn new(rules: &'a RuleTable) -> RegexCompiler<'a> {
+        let reaches = rules.reaches();
+        let min_len = rules.min_lengths();
+        let rules = &rules.rules;
+
+        let mut compiler = RegexCompiler {
+            rules,
+            reaches,
+            min_len,
+            bounded: HashSet::new(),
+            memo: HashMap::new(),
+            stack: vec![],
+        };
+        compiler.bounded = rules
+            .keys()
+            .filter(|rule| {
+                compiler.recursion(**rule) == Recursion::Irregular
+                    || compiler.reaches[rule]
+                        .iter()
+                        .any(|r| compiler.recursion(*r) == Recursion::Irregular)
+            })
+            .copied()
+            .collect();
+        compiler
+    }
+
+    fn recursion(&self, rule: u32) -> Recursion {
+        let reaches_itself = self.reaches.get(&rule).is_some_and(|r| r.contains(&rule));
+        let choices = match self.rules.get(&rule) {
+            Some(RuleOutput::Nonterminal(choices)) if reaches_itself => choices,
+            _ => return Recursion::None,
+        };
+        for choice in choices {
+            let count = choice.iter().filter(|r| **r == rule).count();
+            let others_loop = choice
+                .iter()
+                .any(|r| *r != rule && self.reaches[r].contains(&rule));
+            let at_edge = choice.first() == Some(&rule) || choice.last() == Some(&rule);
+            if others_loop || count > 1 || (count == 1 && !at_edge) {
+                return Recursion::Irregular;
+            }
+        }
+        Recursion::Regular
+    }
+
+    /// Source for `rule`, or None if it can't match anything within `budget` characters.
+    fn rule(&mut self, rule: u32, budget: Option<usize>) -> Result<Option<String>> {
+        let budget = if self.bounded.contains(&rule) {
+            budget
+        } else {
+            None
+        };
+        if let Some(result) = self.memo.get(&(rule, budget)) {
+            return Ok(result.clone());
+        }
+        match self.min_len.get(&rule) {
+            Some(len) if budget.is_none_or(|b| *len <= b) => (),
+            _ => return Ok(None),
+        }
+        if self.stack.contains(&(rule, budget)) {
+            bail!(
+                "Rule {} can refer to itself without matching anything",
+                rule
+            );
+        }
+
+        self.stack.push((rule, budget));
+        let result = match &self.rules[&rule] {
+            RuleOutput::Terminal(class) => Some(class.to_regex()),
+            RuleOutput::Nonterminal(choices) => {
+                let choices = choices.clone();
+                if self.recursion(rule) == Recursion::Regular {
+                    self.regular(rule, &choices, budget)?
+                } else {
+                    let mut alternatives = vec![];
+                    // `rule: rule` adds nothing.
+                    for choice in choices.iter().filter(|c| **c != [rule]) {
+                        if let Some(source) = self.sequence(choice, budget)? {
+                            alternatives.push(source);
+                        }
+                    }
+                    group(&alternatives, "")
+                }
+            }
+        };
+        self.stack.pop();
+
+        if result.as_ref().is_some_and(|r| r.len() > MAX_PATTERN_LEN) {
+            bail!("Regex for rule {} is too big", rule);
+        }
+        self.memo.insert((rule, budget), result.clone());
+        Ok(result)
+    }
+
+    /// `rule` as `(?:prefix)*(?:base)(?:suffix)*`, for `rule: prefix rule | base | rule suffix`.
+    fn regular(
+        &mut self,
+        rule: u32,
+        choices: &[Vec<u32>],
+        budget: Option<usize>,
+    ) -> Result<Option<String>> {
+        let mut prefixes = vec![];
+        let mut bases = vec![];
+        let mut suffixes = vec![];
+        for choice in choices {
+            let (target, rest) = match choice.iter().position(|r| *r == rule) {
+                None => (&mut bases, &choice[..]),
+                // `rule: rule` adds nothing.
+                Some(_) if choice.len() == 1 => continue,
+                Some(0) => (&mut suffixes, &choice[1..]),
+                Some(_) => (&mut prefixes, &choice[..choice.len() - 1]),
             };
+            if let Some(source) = self.sequence(rest, budget)? {
+                target.push(source);
+            }
+        }
+        let base = match group(&bases, "") {
+            Some(base) => base,
+            None => return Ok(None),
+        };
+        let prefixes = group(&prefixes, "*").unwrap_or_default();
+        let suffixes = group(&suffixes, "*").unwrap_or_default();
+        Ok(Some(format!("{}{}{}", prefixes, base, suffixes)))
+    }
+
+    /// Source for the rules one after another, sharing `budget` between them.
+    fn sequence(&mut self, choice: &[u32], budget: Option<usize>) -> Result<Option<String>> {
+        let total: Option<usize> = choice.iter().map(|r| self.min_len.get(r)).sum();
+        let total = match total {
+            Some(total) if budget.is_none_or(|b| total <= b) => total,
+            _ => return Ok(None),
+        };
+        let mut result = String::new();
+        for r in choice {
+            let own = budget.map(|b| b - (total - self.min_len[r]));
+            match self.rule(*r, own)? {
+                Some(source) => result += &source,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(result))
+    }
+}
+
+/// `(?:a|b)` followed by `suffix`, or None for no alternatives.
+fn group(alternatives: &[String], suffix: &str) -> Option<String> {
+    if alternatives.is_empty() {
+        None
+    } else {
+        Some(format!("(?:{}){}", alternatives.join("|"), suffix))
+    }
+}
+
+/// Matches with a regex when the grammar allows one, and with [`RuleTable::parse_tree`] otherwise.
+struct Matcher<'a> {
+    rules: &'a RuleTable,
+    rule_num: u32,
+    regex: Option<RuleRegex>,
+}
+
+impl<'a> Matcher<'a> {
+    fn uses_regex(&self) -> bool {
+        self.regex.is_some()
+    }
+
+    fn is_match(&self, s: &str) -> bool {
+        match self.regex.as_ref().and_then(|regex| regex.is_match(s)) {
+            Some(result) => result,
+            None => self.rules.is_match(self.rule_num, s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Symbol<'a> {
+    Rule(u32),
+    Class(&'a CharClass),
+}
+
+/// Alternative `alt` of `rule`, matched up to `dot` from position `origin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Item {
+    rule: u32,
+    alt: usize,
+    dot: usize,
+    origin: usize,
+}
+
+impl Item {
+    fn start(rule: u32, alt: usize, origin: usize) -> Item {
+        Item {
+            rule,
+            alt,
+            dot: 0,
+            origin,
+        }
+    }
+
+    fn advance(self) -> Item {
+        Item {
+            dot: self.dot + 1,
+            ..self
+        }
+    }
+}
+
+/// What the symbol before the dot matched. Rules point at their completed item by `(set, index)`.
+#[derive(Debug, Clone, Copy)]
+enum Child {
+    Char(char),
+    Rule(usize, usize),
+}
+
+/// An item, with the item it was advanced from and what it was advanced over.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    item: Item,
+    prev: Option<(usize, usize)>,
+    child: Option<Child>,
+}
+
+/// Only the first way of reaching each item is kept. That always points at
+/// entries added before it, so following the pointers can't go round in circles.
+struct Chart {
+    sets: Vec<Vec<Entry>>,
+    seen: Vec<HashSet<Item>>,
+}
+
+impl Chart {
+    fn new(len: usize) -> Chart {
+        Chart {
+            sets: vec![vec![]; len],
+            seen: vec![HashSet::new(); len],
+        }
+    }
+
+    fn add(&mut self, set: usize, item: Item, prev: Option<(usize, usize)>, child: Option<Child>) {
+        if self.seen[set].insert(item) {
+            self.sets[set].push(Entry { item, prev, child });
+        }
+    }
+
+    /// What the completed item at `set`, `idx` matched, with anonymous rules replaced by their children.
+    fn children(&self, rules: &RuleTable, set: usize, idx: usize) -> Vec<ParseTree> {
+        let mut result = vec![];
+        let mut at = Some((set, idx));
+        while let Some((set, idx)) = at {
+            let entry = &self.sets[set][idx];
+            match entry.child {
+                Some(Child::Char(c)) => result.push(ParseTree::Leaf(c)),
+                Some(Child::Rule(set, idx)) => {
+                    let children = self.children(rules, set, idx);
+                    match rules.names.get(&self.sets[set][idx].item.rule) {
+                        Some(name) => result.push(ParseTree::Node {
+                            rule: name.clone(),
+                            children,
+                        }),
+                        None => result.extend(children.into_iter().rev()),
+                    }
+                }
+                None => (),
+            }
+            at = entry.prev;
+        }
+        result.reverse();
+        result
+    }
+}
+
+/// Derivation of a message, printed as `(rule children...)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseTree {
+    Leaf(char),
+    Node {
+        rule: String,
+        children: Vec<ParseTree>,
+    },
+}
+
+impl ParseTree {
+    /// The matched text.
+    fn text(&self) -> String {
+        match self {
+            ParseTree::Leaf(c) => c.to_string(),
+            ParseTree::Node { children, .. } => children.iter().map(ParseTree::text).collect(),
+        }
+    }
+}
+
+impl Display for ParseTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseTree::Leaf(c) => write!(f, "{}", c),
+            ParseTree::Node { rule, children } => {
+                write!(f, "({}", rule)?;
+                for child in children {
+                    write!(f, " {}", child)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Generating strings from the rules, mostly to check the matchers with.
+impl RuleTable {
+    /// Every string `rule_num` matches, shortest first and in order within a length.
+    fn enumerate(&self, rule_num: u32) -> Enumerator<'_> {
+        Enumerator {
+            rules: self,
+            root: rule_num,
+            max_len: self.longest(rule_num),
+            min_len: self.min_lengths(),
+            strings: HashMap::new(),
+            next_len: 0,
+            current: BTreeSet::new().into_iter(),
+        }
+    }
+
+    /// Length of the longest string `rule_num` matches, or None if there is no longest.
+    fn longest(&self, rule_num: u32) -> Option<usize> {
+        let min_len = self.min_lengths();
+        if !min_len.contains_key(&rule_num) {
+            return Some(0);
+        }
+        let choices = |rule: u32| -> Vec<&Vec<u32>> {
+            match self.rules.get(&rule) {
+                Some(RuleOutput::Nonterminal(choices)) => choices
+                    .iter()
+                    .filter(|c| c.iter().all(|r| min_len.contains_key(r)))
+                    .collect(),
+                _ => vec![],
+            }
+        };
+        let reaches = |from: u32| -> HashSet<u32> {
+            let mut seen = HashSet::new();
+            let mut todo = vec![from];
+            while let Some(rule) = todo.pop() {
+                if seen.insert(rule) {
+                    todo.extend(choices(rule).into_iter().flatten());
+                }
+            }
+            seen
+        };
+
+        // Rules which can match something other than the empty string.
+        let mut nonempty: HashSet<u32> = HashSet::new();
+        loop {
+            let before = nonempty.len();
+            for (rule, output) in &self.rules {
+                let grows = match output {
+                    RuleOutput::Terminal(_) => true,
+                    RuleOutput::Nonterminal(_) => choices(*rule)
+                        .iter()
+                        .any(|c| c.iter().any(|r| nonempty.contains(r))),
+                };
+                if grows {
+                    nonempty.insert(*rule);
+                }
+            }
+            if nonempty.len() == before {
+                break;
+            }
+        }
+
+        // Infinite if a rule can come back to itself with something more around it.
+        let useful = reaches(rule_num);
+        for rule in &useful {
+            for choice in choices(*rule) {
+                for (idx, next) in choice.iter().enumerate() {
+                    let grows = choice
+                        .iter()
+                        .enumerate()
+                        .any(|(other, r)| other != idx && nonempty.contains(r));
+                    if grows && reaches(*next).contains(rule) {
+                        return None;
+                    }
+                }
+            }
+        }
+
+        let mut max_len: HashMap<u32, usize> = HashMap::new();
+        loop {
+            let mut changed = false;
+            for rule in &useful {
+                let len = match &self.rules[rule] {
+                    RuleOutput::Terminal(_) => 1,
+                    RuleOutput::Nonterminal(_) => choices(*rule)
+                        .iter()
+                        .map(|c| c.iter().map(|r| max_len.get(r).unwrap_or(&0)).sum())
+                        .max()
+                        .unwrap_or(0),
+                };
+                if max_len.get(rule) != Some(&len) {
+                    max_len.insert(*rule, len);
+                    changed = true;
+                }
+            }
+            if !changed {
+                return max_len.get(&rule_num).copied();
+            }
+        }
+    }
+
+    /// How many strings `rule_num` matches, or None if there are infinitely many.
+    fn language_size(&self, rule_num: u32) -> Option<usize> {
+        self.longest(rule_num)?;
+        Some(self.enumerate(rule_num).count())
+    }
+
+    fn sampler(&self, max_depth: usize) -> Sampler<'_> {
+        let mut heights: HashMap<u32, usize> = HashMap::new();
+        loop {
+            let mut changed = false;
+            for (rule, output) in &self.rules {
+                let height = match output {
+                    RuleOutput::Terminal(_) => Some(1),
+                    RuleOutput::Nonterminal(choices) => choices
+                        .iter()
+                        .filter_map(|c| Sampler::choice_height(&heights, c))
+                        .min(),
+                };
+                if let Some(height) = height {
+                    if heights.get(rule).is_none_or(|old| height < *old) {
+                        heights.insert(*rule, height);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        Sampler {
+            rules: self,
+            heights,
+            max_depth,
+        }
+    }
+
+    /// Checks `rule_num` matches what it generates, with the parser and the regex:
+    /// the first `count` strings, and `count` random ones drawn from `seed`.
+    fn self_test(&self, rule_num: u32, count: usize, seed: u64) -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let sampler = self.sampler(8);
+        let mut strings: Vec<String> = self.enumerate(rule_num).take(count).collect();
+        for _ in 0..count {
+            strings.extend(sampler.sample(rule_num, &mut rng));
+        }
+
+        let matcher = self.matcher(rule_num, &strings);
+        for s in &strings {
+            if !self.is_match(rule_num, s) {
+                bail!("Rule {} doesn't match {:?}", self.name(rule_num), s);
+            }
+            if !matcher.is_match(s) {
+                bail!(
+                    "Regex for rule {} doesn't match {:?}",
+                    self.name(rule_num),
+                    s
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// See [`RuleTable::enumerate`]. Works out the strings of each length in turn,
+/// for just the rules and lengths those need.
+struct Enumerator<'a> {
+    rules: &'a RuleTable,
+    root: u32,
+    min_len: HashMap<u32, usize>,
+    max_len: Option<usize>,
+    /// What each rule matches of each length, once worked out.
+    strings: HashMap<(u32, usize), BTreeSet<String>>,
+    next_len: usize,
+    current: btree_set::IntoIter<String>,
+}
+
+impl<'a> Enumerator<'a> {
+    /// Alternatives of `rule` which can match something.
+    fn choices(&self, rule: u32) -> Vec<&'a Vec<u32>> {
+        match &self.rules.rules[&rule] {
+            RuleOutput::Terminal(_) => vec![],
+            RuleOutput::Nonterminal(choices) => choices
+                .iter()
+                .filter(|c| c.iter().all(|r| self.min_len.contains_key(r)))
+                .collect(),
+        }
+    }
+
+    /// Works out what `rule` matches of length `len`, and whatever that needs.
+    fn fill(&mut self, rule: u32, len: usize) {
+        let mut needed = BTreeSet::new();
+        let mut todo = vec![(rule, len)];
+        while let Some((rule, len)) = todo.pop() {
+            if self.strings.contains_key(&(rule, len)) || !needed.insert((len, rule)) {
+                continue;
+            }
+            for choice in self.choices(rule) {
+                let total: usize = choice.iter().map(|r| self.min_len[r]).sum();
+                if total > len {
+                    continue;
+                }
+                for r in choice {
+                    let min_len = self.min_len[r];
+                    for part in min_len..=len - (total - min_len) {
+                        todo.push((*r, part));
+                    }
+                }
+            }
+        }
+
+        // Shortest first. Rules looping round without adding anything need going
+        // round until nothing changes.
+        let needed: Vec<(usize, u32)> = needed.into_iter().collect();
+        for group in needed.chunk_by(|a, b| a.0 == b.0) {
+            for (len, rule) in group {
+                self.strings.insert((*rule, *len), BTreeSet::new());
+            }
+            loop {
+                let mut changed = false;
+                for (len, rule) in group {
+                    let strings = self.compute(*rule, *len);
+                    if strings.len() > self.strings[&(*rule, *len)].len() {
+                        self.strings.insert((*rule, *len), strings);
+                        changed = true;
+                    }
+                }
+                if !changed {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn compute(&self, rule: u32, len: usize) -> BTreeSet<String> {
+        let mut result = BTreeSet::new();
+        match &self.rules.rules[&rule] {
+            RuleOutput::Terminal(class) if len == 1 => {
+                result.extend(class.chars().map(String::from))
+            }
+            RuleOutput::Terminal(_) => (),
+            RuleOutput::Nonterminal(_) => {
+                for choice in self.choices(rule) {
+                    self.concat(choice, len, &mut String::new(), &mut result);
+                }
+            }
         }
+        result
+    }
+
+    /// Adds `prefix` followed by every way `rules` match exactly `len` characters.
+    fn concat(
+        &self,
+        rules: &[u32],
+        len: usize,
+        prefix: &mut String,
+        result: &mut BTreeSet<String>,
+    ) {
+        let (first, rest) = match rules.split_first() {
+            Some(split) => split,
+            None => {
+                if len == 0 {
+                    result.insert(prefix.clone());
+                }
+                return;
+            }
+        };
+        let rest_len: usize = rest.iter().map(|r| self.min_len[r]).sum();
+        if rest_len > len {
+            return;
+        }
+        for first_len in self.min_len[first]..=len - rest_len {
+            for s in self.strings.get(&(*first, first_len)).into_iter().flatten() {
+                prefix.push_str(s);
+                self.concat(rest, len - first_len, prefix, result);
+                prefix.truncate(prefix.len() - s.len());
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Enumerator<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            if let Some(s) = self.current.next() {
+                return Some(s);
+            }
+            if self.max_len.is_some_and(|max_len| self.next_len > max_len) {
+                return None;
+            }
+            let len = self.next_len;
+            self.next_len += 1;
+            if self.min_len.contains_key(&self.root) {
+                self.fill(self.root, len);
+                self.current = self.strings[&(self.root, len)].clone().into_iter();
+            }
+        }
+    }
+}
+
+/// Random derivations. Past `max_depth` nested rules, it takes whichever
+/// alternative finishes soonest, so recursive rules still come to an end.
+struct Sampler<'a> {
+    rules: &'a RuleTable,
+    /// Height of the shallowest derivation of each rule which can match anything.
+    heights: HashMap<u32, usize>,
+    max_depth: usize,
+}
+
+impl<'a> Sampler<'a> {
+    fn choice_height(heights: &HashMap<u32, usize>, choice: &[u32]) -> Option<usize> {
+        choice
+            .iter()
+            .map(|r| heights.get(r).copied())
+            .try_fold(0, |max, h| h.map(|h| max.max(h)))
+            .map(|h| h + 1)
+    }
+
+    /// None if `rule_num` can't match anything.
+    fn sample<R: Rng>(&self, rule_num: u32, rng: &mut R) -> Option<String> {
+        self.heights.get(&rule_num)?;
+        let mut result = String::new();
+        self.derive(rule_num, 0, rng, &mut result);
+        Some(result)
+    }
+
+    fn derive<R: Rng>(&self, rule_num: u32, depth: usize, rng: &mut R, result: &mut String) {
+        match &self.rules.rules[&rule_num] {
+            RuleOutput::Terminal(class) => {
+                let idx = rng.gen_range(0..class.chars().count());
+                result.extend(class.chars().nth(idx));
+            }
+            RuleOutput::Nonterminal(choices) => {
+                let mut candidates: Vec<(&Vec<u32>, usize)> = choices
+                    .iter()
+                    .filter_map(|c| Sampler::choice_height(&self.heights, c).map(|h| (c, h)))
+                    .collect();
+                if depth >= self.max_depth {
+                    let lowest = self.heights[&rule_num];
+                    candidates.retain(|(_, height)| *height == lowest);
+                }
+                let (choice, _) = candidates[rng.gen_range(0..candidates.len())];
+                for rule in choice {
+                    self.derive(*rule, depth + 1, rng, result);
+                }
+            }
+        }
+    }
+}
+
+fn apply_part2(rules: &mut RuleTable) {
+    rules
+        .rules
+        .insert(8, RuleOutput::Nonterminal(vec![vec![42], vec![42, 8]]));
+    rules.rules.insert(
+        11,
+        RuleOutput::Nonterminal(vec![vec![42, 31], vec![42, 11, 31]]),
+    );
+}
+
+pub struct Day19 {
+    rules: RuleTable,
+    messages: Vec<String>,
+}
+
+impl Solver for Day19 {
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Day19> {
+        let (rules, messages) = RuleTable::parse(input)?;
+        Ok(Day19 { rules, messages })
+    }
 
-        false
+    fn part1(&self) -> Result<usize> {
+        Ok(self.rules.count_matches(0, &self.messages))
+    }
+
+    fn part2(&self) -> Result<usize> {
+        let mut rules = self.rules.clone();
+        apply_part2(&mut rules);
+        Ok(rules.count_matches(0, &self.messages))
     }
 }
 
@@ -188,19 +1206,11 @@ mod tests {
 
     #[test]
     fn day19_smoke1() -> Result<()> {
-        let (raw_rules, messages) = load_day19_file("day19_smoke.txt")?;
-        let rule_0 = build_single_rule(0, &raw_rules)?;
-
-        println!("Day 19 smoke1: {}", rule_0);
-        let mut count = 0;
-        for m in messages {
-            if rule_0.is_match(&m) {
-                // println!("Day 19 smoke1 match {}", m);
-                count += 1;
-            }
-        }
-        println!("Day 19 smoke1: {}", count);
-        assert_eq!(2, count);
+        let (rules, messages) = RuleTable::parse_file("day19_smoke.txt")?;
+        let matcher = rules.matcher(0, &messages);
+        assert!(matcher.uses_regex());
+        assert_eq!(2, messages.iter().filter(|m| matcher.is_match(m)).count());
+        assert_eq!(2, Day19::load("day19_smoke.txt")?.part1()?);
         Ok(())
     }
 
@@ -223,17 +1233,7 @@ mod tests {
 
     #[test]
     fn day19_1() -> Result<()> {
-        let (raw_rules, messages) = load_day19_file("day19.txt")?;
-        let rule_0 = build_single_rule(0, &raw_rules)?;
-
-        // println!("Day 19.1: {}", rule_0);
-        let mut count = 0;
-        for m in messages {
-            if rule_0.is_match(&m) {
-                // println!("Day 19 smoke1 match {}", m);
-                count += 1;
-            }
-        }
+        let count = Day19::load("day19.txt")?.part1()?;
         println!("Day 19.1: {}", count);
         assert_eq!(241, count);
         Ok(())
@@ -255,16 +1255,6 @@ mod tests {
         Ok(())
     }
 
-    fn apply_part2(rules: &mut RuleTable) {
-        rules
-            .0
-            .insert(8, RuleOutput::Nonterminal(vec![vec![42], vec![42, 8]]));
-        rules.0.insert(
-            11,
-            RuleOutput::Nonterminal(vec![vec![42, 31], vec![42, 11, 31]]),
-        );
-    }
-
     #[test]
     fn day19_smoke2() -> Result<()> {
         let (mut rules, messages) = RuleTable::parse_file("day19_smoke2.txt")?;
@@ -298,19 +1288,305 @@ mod tests {
 
     #[test]
     fn day19_2() -> Result<()> {
-        let (mut rules, messages) = RuleTable::parse_file("day19.txt")?;
+        let count = Day19::load("day19.txt")?.part2()?;
+        println!("Day 19.2: {}", count);
+        assert_eq!(424, count);
+        Ok(())
+    }
 
-        apply_part2(&mut rules);
+    #[test]
+    fn day19_left_recursion() -> Result<()> {
+        let (rules, _) = RuleTable::parse("0: 0 1 | 1\n1: \"a\"\n")?;
+        assert!(rules.is_match(0, "a"));
+        assert!(rules.is_match(0, "aaaa"));
+        assert!(!rules.is_match(0, ""));
+        assert!(!rules.is_match(0, "aab"));
 
-        let mut count = 0;
-        for m in messages {
-            if rules.is_match(0, &m) {
-                // println!("Day 19 smoke1 match {}", m);
-                count += 1;
+        let tree = rules.parse_tree(0, "aaa").unwrap();
+        assert_eq!("(0 (0 (0 (1 a)) (1 a)) (1 a))", tree.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn day19_epsilon() -> Result<()> {
+        // a^n b^n, including nothing at all.
+        let (rules, _) = RuleTable::parse("0: 1 0 2 | 3\n1: \"a\"\n2: \"b\"\n3: \"\"\n")?;
+        assert!(rules.is_match(0, ""));
+        assert!(rules.is_match(0, "ab"));
+        assert!(rules.is_match(0, "aaabbb"));
+        assert!(!rules.is_match(0, "aab"));
+        assert!(!rules.is_match(0, "ba"));
+        assert_eq!(
+            "(0 (1 a) (0 (3)) (2 b))",
+            rules.parse_tree(0, "ab").unwrap().to_string()
+        );
+
+        // Empty rules in the middle, and a rule which only matches through an empty one.
+        let (rules, _) = RuleTable::parse("0: 1 2 1 3\n1: \"\"\n2: 1\n3: \"c\"\n")?;
+        assert!(rules.is_match(0, "c"));
+        assert!(!rules.is_match(0, ""));
+        Ok(())
+    }
+
+    #[test]
+    fn day19_ambiguous() -> Result<()> {
+        // Catalan-many ways to split, which a search would try one by one.
+        let (rules, _) = RuleTable::parse("0: 0 0 | 1\n1: \"a\"\n")?;
+        let long = "a".repeat(40);
+        assert!(rules.is_match(0, &long));
+        assert!(!rules.is_match(0, &(long.clone() + "b")));
+        assert_eq!(long, rules.parse_tree(0, &long).unwrap().text());
+
+        // Cycles of rules which match without consuming anything.
+        let (rules, _) = RuleTable::parse("0: 1 | 0\n1: 0 | 2\n2: \"x\"\n")?;
+        assert_eq!(
+            "(0 (1 (2 x)))",
+            rules.parse_tree(0, "x").unwrap().to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn day19_parse_tree() -> Result<()> {
+        let (mut rules, messages) = RuleTable::parse_file("day19_smoke2.txt")?;
+        apply_part2(&mut rules);
+        for m in &messages {
+            if let Some(tree) = rules.parse_tree(0, m) {
+                assert_eq!(*m, tree.text());
+                match tree {
+                    ParseTree::Node { rule, children } => {
+                        assert_eq!("0", rule);
+                        assert_eq!(2, children.len());
+                    }
+                    ParseTree::Leaf(_) => panic!("Rule 0 is not a leaf"),
+                }
             }
         }
-        println!("Day 19.2: {}", count);
-        assert_eq!(424, count);
+        assert!(rules
+            .parse_tree(0, "bbbbbbbbbbbbbbbbbbbbbbbbbbbb")
+            .is_none());
+        assert!(rules.parse_tree(99, "a").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn day19_compile_regex() -> Result<()> {
+        let (rules, messages) = RuleTable::parse_file("day19_smoke.txt")?;
+        let regex = rules.compile_regex(0, None)?;
+        assert_eq!(None, regex.max_len);
+        let count = messages.iter().filter(|m| regex.regex.is_match(m)).count();
+        assert_eq!(2, count);
+
+        // Loops at either end become repetition.
+        let (rules, _) = RuleTable::parse("0: 1 0 | 2 | 0 3\n1: \"a\"\n2: \"b\"\n3: \"c\"\n")?;
+        let regex = rules.compile_regex(0, None)?;
+        assert_eq!("^(?:a)*(?:b)(?:c)*$", regex.regex.as_str());
+        assert_eq!(Some(true), regex.is_match("aabccc"));
+        assert_eq!(Some(false), regex.is_match("aca"));
+        Ok(())
+    }
+
+    #[test]
+    fn day19_unroll_regex() -> Result<()> {
+        let (mut rules, messages) = RuleTable::parse_file("day19_smoke2.txt")?;
+        apply_part2(&mut rules);
+        let e = rules.compile_regex(0, None).unwrap_err();
+        assert_eq!("Rule 11 is not regular", e.to_string());
+
+        let matcher = rules.matcher(0, &messages);
+        assert!(matcher.uses_regex());
+        assert_eq!(12, messages.iter().filter(|m| matcher.is_match(m)).count());
+
+        // a^n b^n, only right up to the length it was unrolled for.
+        let (rules, _) = RuleTable::parse("0: 1 0 2 | 3\n1: \"a\"\n2: \"b\"\n3: \"\"\n")?;
+        let regex = rules.compile_regex(0, Some(6))?;
+        assert_eq!(Some(6), regex.max_len);
+        assert_eq!(Some(true), regex.is_match(""));
+        assert_eq!(Some(true), regex.is_match("aaabbb"));
+        assert_eq!(Some(false), regex.is_match("aabbb"));
+        assert_eq!(None, regex.is_match("aaaabbbb"));
+        let long = ["aaaabbbb".to_owned()];
+        assert!(rules.matcher(0, &long).is_match("aaaabbbb"));
+        Ok(())
+    }
+
+    #[test]
+    fn day19_regex_fallback() -> Result<()> {
+        // Nothing bounds how often 0 repeats, so this needs the parser.
+        let (rules, _) = RuleTable::parse("0: 1 0 1 | 2\n1: \"\"\n2: \"x\"\n")?;
+        let e = rules.compile_regex(0, Some(5)).unwrap_err();
+        assert_eq!(
+            "Rule 0 can refer to itself without matching anything",
+            e.to_string()
+        );
+        let messages = ["x".to_owned()];
+        let matcher = rules.matcher(0, &messages);
+        assert!(!matcher.uses_regex());
+        assert!(matcher.is_match("x"));
+
+        // Exponentially many ways to split makes for a huge regex.
+        let (rules, _) = RuleTable::parse("0: 0 0 | 1\n1: \"a\"\n")?;
+        let messages = ["a".repeat(40)];
+        assert!(rules.compile_regex(0, Some(40)).is_err());
+        let matcher = rules.matcher(0, &messages);
+        assert!(!matcher.uses_regex());
+        assert!(matcher.is_match(&messages[0]));
+
+        let (mut rules, messages) = RuleTable::parse_file("day19.txt")?;
+        apply_part2(&mut rules);
+        assert!(rules.matcher(0, &messages).uses_regex());
+        Ok(())
+    }
+
+    #[test]
+    fn day19_named_rules() -> Result<()> {
+        let input = "greeting: \"HELLO\" sep name
+            sep: \"_\"+
+            name: [A-Z] [a-z0-9]* bang?
+            bang: \"!\"
+
+            HELLO_Bob
+            HELLO__alice7!
+            HELLO__Alice7!
+            HELLO";
+        let (rules, messages) = RuleTable::parse(input)?;
+        let greeting = rules.rule("greeting")?;
+        let matches: Vec<&String> = messages
+            .iter()
+            .filter(|m| rules.is_match(greeting, m))
+            .collect();
+        assert_eq!(vec!["HELLO_Bob", "HELLO__Alice7!"], matches);
+        assert_eq!(
+            "(greeting H E L L O (sep _) (name B o b))",
+            rules.parse_tree(greeting, "HELLO_Bob").unwrap().to_string()
+        );
+        assert!(rules.rule("nobody").is_err());
+
+        let regex = rules.compile_regex(greeting, None)?;
+        assert_eq!(Some(true), regex.is_match("HELLO___Zz9!"));
+        assert_eq!(Some(false), regex.is_match("HELLO_!"));
+
+        // Numbers and names mix, and names get ids past the numbers.
+        let (rules, _) = RuleTable::parse("top: 3 [xyz]\n3: \"ab\" | [0-9]\n")?;
+        let top = rules.rule("top")?;
+        assert_eq!(4, top);
+        assert!(rules.is_match(top, "aby"));
+        assert!(rules.is_match(top, "7z"));
+        assert!(!rules.is_match(top, "a7"));
+
+        let day = Day19::parse("0: top\ntop: 3 [xyz]+\n3: \"ab\" | [0-9]\n\naby\n7zz\na7\n")?;
+        assert_eq!(2, day.part1()?);
+        Ok(())
+    }
+
+    #[test]
+    fn day19_syntax_errors() {
+        let error = |input: &str| format!("{:#}", RuleTable::parse(input).unwrap_err());
+        assert_eq!(
+            "Line 3 is neither a rule nor a message: not a message",
+            error("0: \"a\"\n\nnot a message\n")
+        );
+        assert_eq!("Line 1: 0: 1 ): Unexpected ')'", error("0: 1 )\n1: \"a\""));
+        assert_eq!("Line 2: 1: foo: Unknown rule foo", error("0: 1\n1: foo"));
+        assert_eq!("Line 1: 0: \"ab: Unterminated literal", error("0: \"ab"));
+        assert_eq!("Line 1: 0: [a-: Unfinished range from 'a'", error("0: [a-"));
+        assert_eq!(
+            "Line 1: 0: [z-a]: Range 'z'-'a' is backwards",
+            error("0: [z-a]")
+        );
+        assert_eq!(
+            "Line 1: 0: +\"a\": Nothing to repeat before +",
+            error("0: +\"a\"")
+        );
+        assert_eq!(
+            "Line 1: 0: \"a\" |: Empty alternative, use \"\" to match nothing",
+            error("0: \"a\" |")
+        );
+        assert_eq!(
+            "Line 2: rule 0 is defined twice: 0: \"b\"",
+            error("0: \"a\"\n0: \"b\"")
+        );
+    }
+
+    #[test]
+    fn day19_enumerate() -> Result<()> {
+        let (rules, _) = RuleTable::parse_file("day19_smoke.txt")?;
+        let all: Vec<String> = rules.enumerate(0).collect();
+        assert_eq!(8, all.len());
+        assert_eq!("aaaabb", all[0]);
+        assert!(all.iter().all(|s| rules.is_match(0, s)));
+        assert_eq!(Some(8), rules.language_size(0));
+        assert_eq!(Some(6), rules.longest(0));
+
+        let (rules, _) = RuleTable::parse("0: [ab] [ab]?\n")?;
+        assert_eq!(
+            vec!["a", "b", "aa", "ab", "ba", "bb"],
+            rules.enumerate(0).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(6), rules.language_size(0));
+
+        let (rules, _) = RuleTable::parse("0: 1 0 | 2\n1: \"a\"\n2: \"b\"\n")?;
+        assert_eq!(None, rules.language_size(0));
+        assert_eq!(
+            vec!["b", "ab", "aab", "aaab"],
+            rules.enumerate(0).take(4).collect::<Vec<_>>()
+        );
+
+        let (rules, _) = RuleTable::parse("0: 1 0 2 | \"\"\n1: \"a\"\n2: \"b\"\n")?;
+        assert_eq!(
+            vec!["", "ab", "aabb"],
+            rules.enumerate(0).take(3).collect::<Vec<_>>()
+        );
+
+        // Loops which add nothing keep the language finite.
+        let (rules, _) = RuleTable::parse("0: 1 | \"x\"?\n1: 0 \"\"\n")?;
+        assert_eq!(Some(2), rules.language_size(0));
+        assert_eq!(vec!["", "x"], rules.enumerate(0).collect::<Vec<_>>());
+
+        // And a rule which never finishes matches nothing.
+        let (rules, _) = RuleTable::parse("0: 0 \"a\"\n")?;
+        assert_eq!(Some(0), rules.language_size(0));
+        Ok(())
+    }
+
+    #[test]
+    fn day19_sample() -> Result<()> {
+        let (rules, _) = RuleTable::parse("0: 0 0 | 1\n1: [a-c]\n")?;
+        let sampler = rules.sampler(5);
+        let draw = |seed| -> Vec<String> {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..20)
+                .filter_map(|_| sampler.sample(0, &mut rng))
+                .collect()
+        };
+        let samples = draw(19);
+        assert_eq!(20, samples.len());
+        assert_eq!(samples, draw(19));
+        assert_ne!(samples, draw(20));
+        for s in &samples {
+            assert!(s.len() <= 32, "{} is deeper than the cap", s);
+            assert!(rules.is_match(0, s));
+        }
+
+        let (rules, _) = RuleTable::parse("0: 0 \"a\"\n")?;
+        assert_eq!(
+            None,
+            rules.sampler(5).sample(0, &mut StdRng::seed_from_u64(1))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn day19_self_test() -> Result<()> {
+        let (mut rules, _) = RuleTable::parse_file("day19_smoke2.txt")?;
+        rules.self_test(0, 50, 19)?;
+        apply_part2(&mut rules);
+        rules.self_test(0, 50, 19)?;
+
+        let (rules, _) = RuleTable::parse(
+            "greeting: \"HELLO\" sep name\nsep: \"_\"+\nname: [A-Z] [a-z0-9]* \"!\"?\n",
+        )?;
+        rules.self_test(rules.rule("greeting")?, 100, 7)?;
         Ok(())
     }
 }
@@ -61,38 +61,62 @@ impl PwPolicy {
 }
 
 pub fn load_data(file_name: &str) -> Result<Vec<(PwPolicy, String)>> {
+    parse_data(&read_input(file_name)?)
+}
+
+pub fn parse_data(input: &str) -> Result<Vec<(PwPolicy, String)>> {
     let mut result = vec![];
-    for line in read_file(file_name)? {
-        let line_s = line.context("Could not get line")?;
+    for line_s in input.lines() {
         let parts: Vec<&str> = line_s.split(':').collect();
         let policy = PwPolicy::parse(parts[0])?;
-        result.push((policy, parts[1].trim().to_owned()));
+        let password = parts.get(1).context("Missing password")?;
+        result.push((policy, password.trim().to_owned()));
     }
 
     Ok(result)
 }
 
+pub struct Day2 {
+    passwords: Vec<(PwPolicy, String)>,
+}
+
+impl Solver for Day2 {
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Day2> {
+        Ok(Day2 {
+            passwords: parse_data(input)?,
+        })
+    }
+
+    fn part1(&self) -> Result<usize> {
+        Ok(self
+            .passwords
+            .iter()
+            .filter(|elem| elem.0.is_valid(&elem.1))
+            .count())
+    }
+
+    fn part2(&self) -> Result<usize> {
+        Ok(self
+            .passwords
+            .iter()
+            .filter(|elem| elem.0.is_valid2(&elem.1))
+            .count())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use day2::load_data;
-
-    use crate::*;
+    use super::*;
+    use crate::answers::assert_answer;
 
     #[test]
     fn day_2() -> Result<()> {
-        let input = load_data("day2.txt");
-        let mut valid1 = 0;
-        let mut valid2 = 0;
-        for elem in input? {
-            if elem.0.is_valid(&elem.1) {
-                valid1 += 1;
-            }
-            if elem.0.is_valid2(&elem.1) {
-                valid2 += 1;
-            }
-        }
-        println!("Day 2.1: {}", valid1);
-        println!("Day 2.2: {}", valid2);
+        let input = Day2::load("day2.txt")?;
+        assert_answer(2, 1, "day2.txt", input.part1()?);
+        assert_answer(2, 2, "day2.txt", input.part2()?);
         Ok(())
     }
 }
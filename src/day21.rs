@@ -3,6 +3,8 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
 
+use crate::Solver;
+
 #[derive(Debug, PartialEq, Eq)]
 struct Ingredient {
     name: String,
@@ -24,6 +26,10 @@ struct Day21Input {
 
 impl Day21Input {
     fn parse_input(file_name: &str) -> Result<Day21Input> {
+        Day21Input::parse(&crate::read_input(file_name)?)
+    }
+
+    fn parse(input: &str) -> Result<Day21Input> {
         lazy_static! {
             static ref LINE_RE: Regex = Regex::new(r"^([^(]+) \((.*)\)").unwrap();
         }
@@ -31,9 +37,8 @@ impl Day21Input {
         let mut ingredients = HashMap::new();
         let mut allergens = HashSet::new();
 
-        for line in crate::read_file(file_name)? {
-            let line = line?;
-            let c = LINE_RE.captures(&line).context("Invalid line")?;
+        for line in input.lines() {
+            let c = LINE_RE.captures(line).context("Invalid line")?;
             let ingredient_list = c.get(1).context("Missing ingredients")?.as_str();
             let allergen_list = c.get(2).context("Missing allergens")?.as_str();
 
@@ -128,6 +133,48 @@ impl Day21Input {
         result.sort_by(|a, b| a.1.cmp(b.1));
         Ok(result.iter().map(|a| a.0.to_owned()).collect())
     }
+
+    fn count_safe_ingredients(&self) -> usize {
+        let mut safe_ingredients = vec![];
+        for i in self.ingredients.values() {
+            if i.allergens.is_empty() {
+                safe_ingredients.push(&i.name);
+            }
+        }
+
+        let mut count = 0;
+        for f in &self.food {
+            for s in &safe_ingredients {
+                if f.ingredients.contains(*s) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
+
+pub struct Day21 {
+    input: Day21Input,
+}
+
+impl Solver for Day21 {
+    type Part1 = usize;
+    type Part2 = String;
+
+    fn parse(input: &str) -> Result<Day21> {
+        let mut input = Day21Input::parse(input)?;
+        input.find_allergens()?;
+        Ok(Day21 { input })
+    }
+
+    fn part1(&self) -> Result<usize> {
+        Ok(self.input.count_safe_ingredients())
+    }
+
+    fn part2(&self) -> Result<String> {
+        Ok(self.input.get_canonical_dangers()?.join(","))
+    }
 }
 
 #[cfg(test)]
@@ -174,22 +221,7 @@ mod tests {
         let mut input = Day21Input::parse_input("day21.txt")?;
         input.find_allergens()?;
 
-        let mut safe_ingredients = vec![];
-        for i in input.ingredients.values() {
-            if i.allergens.is_empty() {
-                safe_ingredients.push(&i.name);
-            }
-        }
-
-        let mut count = 0;
-        for f in &input.food {
-            for s in &safe_ingredients {
-                if f.ingredients.contains(*s) {
-                    count += 1;
-                }
-            }
-        }
-        assert_eq!(2280, count);
+        assert_eq!(2280, input.count_safe_ingredients());
         let dangers = input.get_canonical_dangers()?;
         println!("Day 21.2 Dangers: {:?}", dangers);
         assert_eq!(
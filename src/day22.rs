@@ -2,13 +2,18 @@ use std::collections::{HashMap, HashSet, VecDeque};
 
 use anyhow::{bail, Context, Result};
 
+use crate::Solver;
+
 fn load_decks(file_name: &str) -> Result<(VecDeque<u32>, VecDeque<u32>)> {
+    parse_decks(&crate::read_input(file_name)?)
+}
+
+fn parse_decks(input: &str) -> Result<(VecDeque<u32>, VecDeque<u32>)> {
     let mut player1 = VecDeque::new();
     let mut player2 = VecDeque::new();
 
     let mut first_player = true;
-    for line in crate::read_file(file_name)? {
-        let line = line?;
+    for line in input.lines() {
         let line = line.trim();
         if line.is_empty() {
             continue;
@@ -65,6 +70,7 @@ fn score_deck(deck: &[u32]) -> u64 {
     result
 }
 
+#[allow(clippy::only_used_in_recursion)]
 fn play_recursive_combat(
     player1: &mut VecDeque<u32>,
     player2: &mut VecDeque<u32>,
@@ -139,10 +145,58 @@ fn play_recursive_combat(
     }
 }
 
+pub struct Day22 {
+    player1: VecDeque<u32>,
+    player2: VecDeque<u32>,
+}
+
+impl Solver for Day22 {
+    type Part1 = u64;
+    type Part2 = u64;
+
+    fn parse(input: &str) -> Result<Day22> {
+        let (player1, player2) = parse_decks(input)?;
+        Ok(Day22 { player1, player2 })
+    }
+
+    fn part1(&self) -> Result<u64> {
+        part1(self.player1.clone(), self.player2.clone())
+    }
+
+    fn part2(&self) -> Result<u64> {
+        part2(self.player1.clone(), self.player2.clone())
+    }
+}
+
+fn part1(mut player1: VecDeque<u32>, mut player2: VecDeque<u32>) -> Result<u64> {
+    while play_turn(&mut player1, &mut player2)? > 0 {
+        // NOP
+    }
+    player1.make_contiguous();
+    player2.make_contiguous();
+    if player1.is_empty() {
+        Ok(score_deck(player2.as_slices().0))
+    } else {
+        Ok(score_deck(player1.as_slices().0))
+    }
+}
+
+fn part2(mut player1: VecDeque<u32>, mut player2: VecDeque<u32>) -> Result<u64> {
+    let mut memo = HashMap::new();
+
+    let result = play_recursive_combat(&mut player1, &mut player2, &mut memo, 1)?;
+
+    player1.make_contiguous();
+    player2.make_contiguous();
+    match result {
+        1 => Ok(score_deck(player1.as_slices().0)),
+        2 => Ok(score_deck(player2.as_slices().0)),
+        _ => bail!("Invalid result"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
     use super::*;
 
     #[test]
@@ -156,12 +210,11 @@ mod tests {
         }
         player1.make_contiguous();
         player2.make_contiguous();
-        let winning_score;
-        if player1.is_empty() {
-            winning_score = score_deck(player2.as_slices().0);
+        let winning_score = if player1.is_empty() {
+            score_deck(player2.as_slices().0)
         } else {
-            winning_score = score_deck(player1.as_slices().0);
-        }
+            score_deck(player1.as_slices().0)
+        };
 
         assert_eq!(306, winning_score);
         assert_eq!(29, turn_count);
@@ -179,12 +232,11 @@ mod tests {
         }
         player1.make_contiguous();
         player2.make_contiguous();
-        let winning_score;
-        if player1.is_empty() {
-            winning_score = score_deck(player2.as_slices().0);
+        let winning_score = if player1.is_empty() {
+            score_deck(player2.as_slices().0)
         } else {
-            winning_score = score_deck(player1.as_slices().0);
-        }
+            score_deck(player1.as_slices().0)
+        };
 
         assert_eq!(34255, winning_score);
         assert_eq!(133, turn_count);
@@ -226,20 +278,7 @@ mod tests {
 
     #[test]
     fn day22_2() -> Result<()> {
-        let (mut player1, mut player2) = load_decks("day22.txt")?;
-        let mut memo = HashMap::new();
-
-        let result = play_recursive_combat(&mut player1, &mut player2, &mut memo, 1)?;
-
-        player1.make_contiguous();
-        player2.make_contiguous();
-        let winning_score = match result {
-            1 => score_deck(player1.as_slices().0),
-            2 => score_deck(player2.as_slices().0),
-            _ => bail!("Invalid result"),
-        };
-
-        assert_eq!(33369, winning_score);
+        assert_eq!(33369, Day22::load("day22.txt")?.part2()?);
 
         Ok(())
     }
@@ -1,6 +1,8 @@
 use anyhow::{bail, Context, Result};
 use std::{fmt::Display, str::FromStr};
 
+use crate::Solver;
+
 struct Cups {
     cups: [u8; 9],
     current_cup: u8,
@@ -185,11 +187,18 @@ impl FromStr for Cups {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut cups = [0u8; 9];
-        if s.len() > 9 {
-            bail!("Too many cups");
+        if s.chars().count() != 9 {
+            bail!("Need nine cups, not {:?}", s);
         }
         for (idx, c) in s.chars().enumerate() {
-            cups[idx] = c as u8 - b'0';
+            let label = match c.to_digit(10) {
+                Some(d) if d > 0 => d as u8,
+                _ => bail!("Cup {:?} isn't labelled 1 to 9", c),
+            };
+            if cups.contains(&label) {
+                bail!("Cup {} appears twice", label);
+            }
+            cups[idx] = label;
         }
 
         let current_cup = cups[0];
@@ -211,6 +220,36 @@ impl Display for Cups {
     }
 }
 
+pub struct Day23 {
+    labels: String,
+}
+
+impl Solver for Day23 {
+    type Part1 = String;
+    type Part2 = String;
+
+    fn parse(input: &str) -> Result<Day23> {
+        let labels = input.lines().next().context("No cups")?.trim();
+        // Validate up front so both parts can rely on it.
+        labels.parse::<Cups>()?;
+        Ok(Day23 {
+            labels: labels.to_owned(),
+        })
+    }
+
+    fn part1(&self) -> Result<String> {
+        let mut cups: Cups = self.labels.parse()?;
+        for _ in 0..100 {
+            cups.turn()?;
+        }
+        Ok(cups.answer())
+    }
+
+    fn part2(&self) -> Result<String> {
+        bail!("Day 23 part 2 is not implemented")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -238,12 +277,22 @@ mod tests {
     }
 
     #[test]
-    fn day23_1() -> Result<()> {
-        let mut cups: Cups = "186524973".parse()?;
-        for _ in 0..100 {
-            cups.turn()?;
+    fn day23_bad_labels() {
+        for labels in &[
+            "12",
+            "12a",
+            "3891254670",
+            "38912546a",
+            "389125460",
+            "389125463",
+        ] {
+            assert!(Day23::parse(labels).is_err(), "{}", labels);
         }
-        assert_eq!("67384529", &cups.answer());
+    }
+
+    #[test]
+    fn day23_1() -> Result<()> {
+        assert_eq!("45983627", &Day23::load("day23.txt")?.part1()?);
 
         Ok(())
     }
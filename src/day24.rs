@@ -1,17 +1,18 @@
 use anyhow::{bail, Result};
 use std::{collections::HashSet, str::Chars};
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-struct Coord {
-    x: i32,
-    y: i32,
-}
+use crate::{
+    grid::{self, Neighborhood, Point},
+    Solver,
+};
 
-impl Coord {
-    fn origin() -> Coord {
-        Coord { x: 0, y: 0 }
-    }
-}
+/// Hex tiles in "doubled" coordinates: east/west moves two columns, the diagonals move one.
+type Coord = Point;
+
+const ORIGIN: Coord = (0, 0);
+
+const HEX: [Point; 6] = [(2, 0), (1, -1), (-1, -1), (-2, 0), (-1, 1), (1, 1)];
+const HEX_NEIGHBORHOOD: Neighborhood = Neighborhood::Custom(&HEX);
 
 enum Direction {
     East,
@@ -24,30 +25,15 @@ enum Direction {
 
 impl Direction {
     fn step(&self, coord: &Coord) -> Coord {
-        let mut coord = *coord;
-
-        match self {
-            Direction::East => coord.x += 2,
-            Direction::SouthEast => {
-                coord.x += 1;
-                coord.y -= 1;
-            }
-            Direction::SouthWest => {
-                coord.x -= 1;
-                coord.y -= 1;
-            }
-            Direction::West => coord.x -= 2,
-            Direction::NorthWest => {
-                coord.x -= 1;
-                coord.y += 1;
-            }
-            Direction::NorthEast => {
-                coord.x += 1;
-                coord.y += 1;
-            }
-        }
-
-        coord
+        let offset = match self {
+            Direction::East => HEX[0],
+            Direction::SouthEast => HEX[1],
+            Direction::SouthWest => HEX[2],
+            Direction::West => HEX[3],
+            Direction::NorthWest => HEX[4],
+            Direction::NorthEast => HEX[5],
+        };
+        grid::add(*coord, offset)
     }
 
     fn next(chars: &mut Chars) -> Result<Option<Direction>> {
@@ -89,7 +75,7 @@ impl Direction {
 fn follow_path(floor: &mut HashSet<Coord>, path: &str) -> Result<()> {
     let mut chars = path.chars();
 
-    let mut location = Coord::origin();
+    let mut location = ORIGIN;
 
     while let Some(d) = Direction::next(&mut chars)? {
         location = d.step(&location);
@@ -107,15 +93,14 @@ fn follow_path(floor: &mut HashSet<Coord>, path: &str) -> Result<()> {
 fn generation(floor: &HashSet<Coord>) -> HashSet<Coord> {
     let mut result = HashSet::new();
 
-    for coord in floor.iter().flat_map(|coord| neighborhood(coord)) {
+    for coord in floor.iter().flat_map(neighborhood) {
         let old_value = floor.contains(&coord);
         let count = count_neighbors(floor, &coord);
-        let new_value;
-        if old_value {
-            new_value = count == 1 || count == 2;
+        let new_value = if old_value {
+            count == 1 || count == 2
         } else {
-            new_value = count == 2;
-        }
+            count == 2
+        };
         if new_value {
             result.insert(coord);
         }
@@ -125,26 +110,51 @@ fn generation(floor: &HashSet<Coord>) -> HashSet<Coord> {
 }
 
 fn count_neighbors(floor: &HashSet<Coord>, coord: &Coord) -> usize {
-    let mut result = 0;
-    for c in neighborhood(coord) {
-        if coord != &c && floor.contains(&c) {
-            result += 1;
-        }
+    HEX_NEIGHBORHOOD
+        .around(*coord)
+        .filter(|c| floor.contains(c))
+        .count()
+}
+
+/// `coord` itself and its six neighbors.
+fn neighborhood(coord: &Coord) -> impl Iterator<Item = Coord> {
+    std::iter::once(*coord).chain(HEX_NEIGHBORHOOD.around(*coord))
+}
+
+fn parse_floor(input: &str) -> Result<HashSet<Coord>> {
+    let mut floor = HashSet::new();
+
+    for l in input.lines() {
+        follow_path(&mut floor, l)?;
     }
-    result
+    Ok(floor)
 }
 
-fn neighborhood(coord: &Coord) -> Vec<Coord> {
-    let mut result = vec![];
-    result.push(*coord);
-    result.push(Direction::East.step(&coord));
-    result.push(Direction::SouthEast.step(&coord));
-    result.push(Direction::SouthWest.step(&coord));
-    result.push(Direction::West.step(&coord));
-    result.push(Direction::NorthWest.step(&coord));
-    result.push(Direction::NorthEast.step(&coord));
+pub struct Day24 {
+    floor: HashSet<Coord>,
+}
 
-    result
+impl Solver for Day24 {
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Day24> {
+        Ok(Day24 {
+            floor: parse_floor(input)?,
+        })
+    }
+
+    fn part1(&self) -> Result<usize> {
+        Ok(self.floor.len())
+    }
+
+    fn part2(&self) -> Result<usize> {
+        let mut floor = self.floor.clone();
+        for _day in 1..101 {
+            floor = generation(&floor);
+        }
+        Ok(floor.len())
+    }
 }
 
 #[cfg(test)]
@@ -161,11 +171,7 @@ mod tests {
     // }
     #[test]
     fn day24_smoke1() -> Result<()> {
-        let mut floor = HashSet::new();
-
-        for l in crate::read_file("day24_smoke.txt")? {
-            follow_path(&mut floor, &l?)?;
-        }
+        let floor = parse_floor(&crate::read_input("day24_smoke.txt")?)?;
 
         println!("Foo: {:?}", floor);
         let count = floor.len();
@@ -176,25 +182,13 @@ mod tests {
 
     #[test]
     fn day24_1() -> Result<()> {
-        let mut floor = HashSet::new();
-
-        for l in crate::read_file("day24.txt")? {
-            follow_path(&mut floor, &l?)?;
-        }
-
-        let count = floor.len();
-
-        assert_eq!(312, count);
+        assert_eq!(312, Day24::load("day24.txt")?.part1()?);
         Ok(())
     }
 
     #[test]
     fn day24_smoke2() -> Result<()> {
-        let mut floor = HashSet::new();
-
-        for l in crate::read_file("day24_smoke.txt")? {
-            follow_path(&mut floor, &l?)?;
-        }
+        let mut floor = parse_floor(&crate::read_input("day24_smoke.txt")?)?;
 
         // println!("Foo: {:?}", floor);
         let count = floor.len();
@@ -213,24 +207,7 @@ mod tests {
 
     #[test]
     fn day24_2() -> Result<()> {
-        let mut floor = HashSet::new();
-
-        for l in crate::read_file("day24.txt")? {
-            follow_path(&mut floor, &l?)?;
-        }
-
-        // println!("Foo: {:?}", floor);
-        let count = floor.len();
-        assert_eq!(312, count);
-        for _day in 1..11 {
-            floor = generation(&floor);
-            // println!("Day {}: {}", _day, floor.len());
-        }
-        for _day in 11..101 {
-            floor = generation(&floor);
-            // println!("Day {}: {}", _day, floor.len());
-        }
-        assert_eq!(3733, floor.len());
+        assert_eq!(3733, Day24::load("day24.txt")?.part2()?);
         Ok(())
     }
 }
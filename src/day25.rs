@@ -1,10 +1,14 @@
 use std::collections::HashMap;
 
+use anyhow::{bail, Context, Result};
+
+use crate::Solver;
+
 static GENERATOR: u128 = 7;
 static MODULO: u128 = 20201227;
 
 // https://en.wikipedia.org/wiki/Baby-step_giant-step
-fn fast_dlp(base: u128, modulo: u128, target: u128) -> u128 {
+fn fast_dlp(base: u128, modulo: u128, target: u128) -> Result<u128> {
     let (alpha, n, beta) = (base, modulo, target);
     // 1. m ← Ceiling(√n)
     let m = (n as f64).sqrt().ceil() as u128;
@@ -31,24 +35,25 @@ fn fast_dlp(base: u128, modulo: u128, target: u128) -> u128 {
         // Check to see if γ is the second component (αj) of any pair in the table.
         if let Some(j) = table.get(&gamma) {
             // If so, return im + j.
-            return (i * m + j) % n;
+            return Ok((i * m + j) % n);
         }
         // If not, γ ← γ • α−m.
         gamma = (gamma * a_neg_m) % n;
     }
 
-    panic!("No DLP found");
+    bail!("No DLP found for {}", target);
 }
 
-fn brute_force_dlp(base: u128, modulo: u128, target: u128) -> u128 {
-    let mut result = 0;
+/// Powers of `base` repeat within `modulo` steps, so the search stops there.
+fn brute_force_dlp(base: u128, modulo: u128, target: u128) -> Result<u128> {
     let mut power = 1;
-
-    while power != target {
+    for result in 0..modulo {
+        if power == target {
+            return Ok(result);
+        }
         power = (power * base) % modulo;
-        result += 1;
     }
-    result
+    bail!("No DLP found for {}", target);
 }
 
 fn mod_exp(base: u128, exponent: u128, modulo: u128) -> u128 {
@@ -57,7 +62,7 @@ fn mod_exp(base: u128, exponent: u128, modulo: u128) -> u128 {
     } else if exponent == 1 {
         base
     } else {
-        let root = mod_exp(base, exponent >> 1, modulo) as u128;
+        let root = mod_exp(base, exponent >> 1, modulo);
         if exponent % 2 == 1 {
             base * root * root % modulo
         } else {
@@ -66,18 +71,52 @@ fn mod_exp(base: u128, exponent: u128, modulo: u128) -> u128 {
     }
 }
 
+pub struct Day25 {
+    card_public: u128,
+    door_public: u128,
+}
+
+impl Solver for Day25 {
+    type Part1 = u128;
+    type Part2 = u128;
+
+    fn parse(input: &str) -> Result<Day25> {
+        let mut lines = input.lines();
+        let card_public = lines.next().context("Missing card key")?.trim().parse()?;
+        let door_public = lines.next().context("Missing door key")?.trim().parse()?;
+        for key in &[card_public, door_public] {
+            if *key >= MODULO {
+                bail!("Public key {} must be below {}", key, MODULO);
+            }
+        }
+        Ok(Day25 {
+            card_public,
+            door_public,
+        })
+    }
+
+    fn part1(&self) -> Result<u128> {
+        let card_private = fast_dlp(GENERATOR, MODULO, self.card_public)?;
+        Ok(mod_exp(self.door_public, card_private, MODULO))
+    }
+
+    fn part2(&self) -> Result<u128> {
+        bail!("Day 25 has no second puzzle")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn day25_smoke1() {
+    fn day25_smoke1() -> Result<()> {
         let card_public = 5764801;
         let door_public = 17807724;
 
-        let card_private = brute_force_dlp(GENERATOR, MODULO, card_public);
+        let card_private = brute_force_dlp(GENERATOR, MODULO, card_public)?;
         assert_eq!(8, card_private);
-        let door_private = brute_force_dlp(GENERATOR, MODULO, door_public);
+        let door_private = brute_force_dlp(GENERATOR, MODULO, door_public)?;
         assert_eq!(11, door_private);
 
         let shared_secret = mod_exp(door_public, card_private, MODULO);
@@ -85,30 +124,32 @@ mod tests {
 
         let shared_secret = mod_exp(card_public, door_private, MODULO);
         assert_eq!(14897079, shared_secret);
+        Ok(())
     }
 
     #[test]
-    fn day25_1() {
+    fn day25_1() -> Result<()> {
         let card_public = 10212254;
         let door_public = 12577395;
 
-        let card_private = brute_force_dlp(GENERATOR, MODULO, card_public);
+        let card_private = brute_force_dlp(GENERATOR, MODULO, card_public)?;
         println!("Card Private: {}", card_private);
         // Next two aren't necessary, I just was curious
         // let door_private = brute_force_dlp(GENERATOR, MODULO, door_public);
         // println!("Door Private: {}", door_private);
         let shared_secret = mod_exp(door_public, card_private, MODULO);
         assert_eq!(290487, shared_secret);
+        Ok(())
     }
 
     #[test]
-    fn day25_fast_smoke1() {
+    fn day25_fast_smoke1() -> Result<()> {
         let card_public = 5764801;
         let door_public = 17807724;
 
-        let card_private = fast_dlp(GENERATOR, MODULO, card_public);
+        let card_private = fast_dlp(GENERATOR, MODULO, card_public)?;
         assert_eq!(8, card_private);
-        let door_private = fast_dlp(GENERATOR, MODULO, door_public);
+        let door_private = fast_dlp(GENERATOR, MODULO, door_public)?;
         assert_eq!(11, door_private);
 
         let shared_secret = mod_exp(door_public, card_private, MODULO);
@@ -116,19 +157,29 @@ mod tests {
 
         let shared_secret = mod_exp(card_public, door_private, MODULO);
         assert_eq!(14897079, shared_secret);
+        Ok(())
     }
 
     #[test]
-    fn day25_fast_1() {
+    fn day25_fast_1() -> Result<()> {
         let card_public = 10212254;
         let door_public = 12577395;
 
-        let card_private = fast_dlp(GENERATOR, MODULO, card_public);
+        let card_private = fast_dlp(GENERATOR, MODULO, card_public)?;
         println!("Card Private: {}", card_private);
         // Next two aren't necessary, I just was curious
         // let door_private = fast_dlp(GENERATOR, MODULO, door_public);
         // println!("Door Private: {}", door_private);
         let shared_secret = mod_exp(door_public, card_private, MODULO);
         assert_eq!(290487, shared_secret);
+        Ok(())
+    }
+
+    #[test]
+    fn day25_no_dlp() {
+        assert!(brute_force_dlp(GENERATOR, MODULO, 0).is_err());
+        assert!(fast_dlp(GENERATOR, MODULO, 0).is_err());
+        assert!(Day25::parse("0\n5764801\n").unwrap().part1().is_err());
+        assert!(Day25::parse("20201227\n5764801\n").is_err());
     }
 }
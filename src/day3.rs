@@ -1,91 +1,97 @@
 use anyhow::Result;
-use std::collections::HashMap;
 
+use crate::grid::{Edge, Grid};
 use crate::*;
 
+/// The slope repeats to the right but ends at the bottom.
 #[derive(Debug)]
 struct TreeMap {
-    width: usize,
-    height: usize,
-    trees: HashMap<(usize, usize), char>,
+    trees: Grid<bool>,
 }
 
 impl TreeMap {
     fn read_file(file_name: &str) -> Result<TreeMap> {
-        let lines = read_file(file_name)?;
-        let mut width = 0;
-        let mut height = 0;
-        let mut trees = HashMap::new();
-        for line in lines {
-            width = 0;
-            let line = line?;
-            let line = line.trim();
-            for unit in line.chars() {
-                trees.insert((width, height), unit);
-                width += 1;
-            }
-            height += 1;
-        }
-        Ok(TreeMap {
-            width,
-            height,
-            trees,
-        })
+        TreeMap::parse(&read_input(file_name)?)
     }
 
-    fn get_plant(&self, x: usize, y: usize) -> &char {
-        self.trees.get(&(x % self.width, y)).unwrap_or(&' ')
+    fn parse(input: &str) -> Result<TreeMap> {
+        let trees = input.parse::<Grid<bool>>()?;
+        Ok(TreeMap {
+            trees: trees.with_edges(Edge::Wrap, Edge::Bounded),
+        })
     }
 
     fn count_trees(&self, x_diff: usize, y_diff: usize) -> u32 {
         let mut tree_count = 0;
         let mut x = 0;
         let mut y = 0;
-        loop {
-            tree_count += match self.get_plant(x, y) {
-                '.' => 0,
-                '#' => 1,
-                _ => break,
-            };
-            x += x_diff;
-            y += y_diff;
+        while let Some(tree) = self.trees.get((x, y)) {
+            if *tree {
+                tree_count += 1;
+            }
+            x += x_diff as i32;
+            y += y_diff as i32;
         }
         tree_count
     }
 }
 
+pub struct Day3 {
+    map: TreeMap,
+}
+
+impl Solver for Day3 {
+    type Part1 = u32;
+    type Part2 = u64;
+
+    fn parse(input: &str) -> Result<Day3> {
+        Ok(Day3 {
+            map: TreeMap::parse(input)?,
+        })
+    }
+
+    fn part1(&self) -> Result<u32> {
+        Ok(self.map.count_trees(3, 1))
+    }
+
+    fn part2(&self) -> Result<u64> {
+        let map = &self.map;
+
+        let mut product: u64 = 1;
+        product *= map.count_trees(1, 1) as u64;
+        product *= map.count_trees(3, 1) as u64;
+        product *= map.count_trees(5, 1) as u64;
+        product *= map.count_trees(7, 1) as u64;
+        product *= map.count_trees(1, 2) as u64;
+        Ok(product)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::answers::assert_answer;
 
     #[test]
     fn day3_smoke() -> Result<()> {
         let map = TreeMap::read_file("day3_smoke.txt")?;
 
         println!("Day3 Smoke: {}", map.count_trees(3, 1));
+        assert_eq!(7, map.count_trees(3, 1));
 
         Ok(())
     }
 
     #[test]
     fn day3_1() -> Result<()> {
-        let map = TreeMap::read_file("day3.txt")?;
-        println!("Day3.1: {}", map.count_trees(3, 1));
+        assert_answer(3, 1, "day3.txt", Day3::load("day3.txt")?.part1()?);
 
         Ok(())
     }
 
     #[test]
     fn day3_2() -> Result<()> {
-        let map = TreeMap::read_file("day3.txt")?;
-
-        let mut product: u64 = 1;
-        product *= map.count_trees(1, 1) as u64;
-        product *= map.count_trees(3, 1) as u64;
-        product *= map.count_trees(5, 1) as u64;
-        product *= map.count_trees(7, 1) as u64;
-        product *= map.count_trees(1, 2) as u64;
-        println!("Day3.2: {}", product);
+        assert_answer(3, 2, "day3.txt", Day3::load("day3.txt")?.part2()?);
 
         Ok(())
     }
@@ -1,19 +1,22 @@
-use crate::read_file;
+use crate::{read_input, Solver};
 use anyhow::{Context, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::HashMap;
 
 fn load_passports(file_name: &str) -> Result<Vec<HashMap<String, String>>> {
-    let lines = read_file(file_name)?;
+    let passports = parse_passports(&read_input(file_name)?)?;
+    Ok(passports.into_iter().filter(passport_valid).collect())
+}
+
+fn parse_passports(input: &str) -> Result<Vec<HashMap<String, String>>> {
     let mut result = vec![];
     let mut curr = HashMap::new();
 
-    for line in lines {
-        let line = line?;
+    for line in input.lines() {
         let line = line.trim();
         if line.is_empty() {
-            if passport_valid(&curr) {
+            if !curr.is_empty() {
                 result.push(curr);
             }
             curr = HashMap::new();
@@ -25,18 +28,24 @@ fn load_passports(file_name: &str) -> Result<Vec<HashMap<String, String>>> {
                     continue;
                 }
                 // println!("Part: >{}< ", part);
-                curr.insert(part[0..3].to_owned(), part[4..].to_owned());
+                let (key, value) = part.split_at(part.find(':').context("Missing ':'")?);
+                curr.insert(key.to_owned(), value[1..].to_owned());
             }
         }
-        if passport_valid(&curr) {
-            result.push(curr);
-            curr = HashMap::new();
-        }
+    }
+    if !curr.is_empty() {
+        result.push(curr);
     }
 
     Ok(result)
 }
 
+fn passport_complete(passport: &HashMap<String, String>) -> bool {
+    ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"]
+        .iter()
+        .all(|field| passport.contains_key(*field))
+}
+
 fn passport_valid(passport: &HashMap<String, String>) -> bool {
     lazy_static! {
         static ref YEAR_RE: Regex = Regex::new(r"^\d\d\d\d$").unwrap();
@@ -58,7 +67,7 @@ fn passport_valid(passport: &HashMap<String, String>) -> bool {
         return false;
     } else {
         let byr: i32 = byr.parse().unwrap_or(0);
-        if byr < 1920 || byr > 2002 {
+        if !(1920..=2002).contains(&byr) {
             return false;
         }
     }
@@ -66,7 +75,7 @@ fn passport_valid(passport: &HashMap<String, String>) -> bool {
         return false;
     } else {
         let iyr: i32 = iyr.parse().unwrap();
-        if iyr < 2010 || iyr > 2020 {
+        if !(2010..=2020).contains(&iyr) {
             return false;
         }
     }
@@ -74,7 +83,7 @@ fn passport_valid(passport: &HashMap<String, String>) -> bool {
         return false;
     } else {
         let eyr: i32 = eyr.parse().unwrap_or(0);
-        if eyr < 2020 || eyr > 2030 {
+        if !(2020..=2030).contains(&eyr) {
             return false;
         }
     }
@@ -96,12 +105,12 @@ fn passport_valid(passport: &HashMap<String, String>) -> bool {
             .unwrap_or("fake_unit");
         match hgt_str {
             "cm" => {
-                if hgt_val < 150 || hgt_val > 193 {
+                if !(150..=193).contains(&hgt_val) {
                     return false;
                 }
             }
             "in" => {
-                if hgt_val < 59 || hgt_val > 76 {
+                if !(59..=76).contains(&hgt_val) {
                     return false;
                 }
             }
@@ -123,14 +132,52 @@ fn passport_valid(passport: &HashMap<String, String>) -> bool {
     true
 }
 
+pub struct Day4 {
+    passports: Vec<HashMap<String, String>>,
+}
+
+impl Solver for Day4 {
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Day4> {
+        Ok(Day4 {
+            passports: parse_passports(input)?,
+        })
+    }
+
+    fn part1(&self) -> Result<usize> {
+        Ok(self
+            .passports
+            .iter()
+            .filter(|p| passport_complete(p))
+            .count())
+    }
+
+    fn part2(&self) -> Result<usize> {
+        Ok(self.passports.iter().filter(|p| passport_valid(p)).count())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::answers::assert_answer;
+
+    #[test]
+    fn day4_smoke() -> Result<()> {
+        let input = Day4::load("day4_smoke.txt")?;
+        assert_eq!(4, input.part1()?);
+        assert_eq!(4, input.part2()?);
+        assert_eq!(4, load_passports("day4_smoke.txt")?.len());
+        Ok(())
+    }
 
     #[test]
     fn day4_1() -> Result<()> {
-        let passports = load_passports("day4.txt")?;
-        println!("Day4.2: {}", passports.len());
+        let input = Day4::load("day4.txt")?;
+        assert_answer(4, 1, "day4.txt", input.part1()?);
+        assert_answer(4, 2, "day4.txt", input.part2()?);
 
         Ok(())
     }
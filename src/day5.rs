@@ -1,4 +1,8 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, collections::HashMap};
+
+use anyhow::{bail, Result};
+
+use crate::Solver;
 
 #[derive(Eq)]
 struct BoardingPass {
@@ -9,34 +13,36 @@ struct BoardingPass {
 }
 
 impl BoardingPass {
-    fn parse(code: &str) -> BoardingPass {
+    fn parse(code: &str) -> Result<BoardingPass> {
         let code = code.trim().to_owned();
-        assert_eq!(code.len(), 10);
+        if code.chars().count() != 10 {
+            bail!("Boarding pass {:?} should be 10 characters long", code);
+        }
 
         let mut row = 0;
         let mut col = 0;
 
         let mut chars = code.chars();
-        for _ in 0..7 {
+        for c in chars.by_ref().take(7) {
             row <<= 1;
-            row += match chars.next().unwrap() {
+            row += match c {
                 'B' => 1,
                 'F' => 0,
-                _ => panic!("Wrong code"),
+                _ => bail!("Wrong row {:?} in boarding pass {}", c, code),
             };
         }
         let row = row;
-        for _ in 0..3 {
+        for c in chars {
             col <<= 1;
-            col += match chars.next().unwrap() {
+            col += match c {
                 'R' => 1,
                 'L' => 0,
-                _ => panic!("Wrong code"),
+                _ => bail!("Wrong column {:?} in boarding pass {}", c, code),
             };
         }
         let col = col;
         let id = (row as u16) * 8 + (col as u16);
-        BoardingPass { code, row, col, id }
+        Ok(BoardingPass { code, row, col, id })
     }
 }
 
@@ -58,48 +64,39 @@ impl PartialEq for BoardingPass {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::read_file;
-    use anyhow::Result;
-    use std::collections::HashMap;
-
-    #[test]
-    fn day5_smoke() {
-        let pass = BoardingPass::parse("FBFBBFFRLR");
-        assert_eq!(44, pass.row);
-        assert_eq!(5, pass.col);
-        assert_eq!(357, pass.id);
+pub struct Day5 {
+    passes: Vec<BoardingPass>,
+}
 
-        let pass = BoardingPass::parse("BFFFBBFRRR");
-        assert_eq!(70, pass.row);
-        assert_eq!(7, pass.col);
-        assert_eq!(567, pass.id);
+impl Solver for Day5 {
+    type Part1 = u16;
+    type Part2 = u16;
+
+    fn parse(input: &str) -> Result<Day5> {
+        Ok(Day5 {
+            passes: input
+                .lines()
+                .map(BoardingPass::parse)
+                .collect::<Result<_>>()?,
+        })
     }
 
-    #[test]
-    fn day5_1() -> Result<()> {
+    fn part1(&self) -> Result<u16> {
         let mut highest_id = 0;
-        for line in read_file("day5.txt")? {
-            let pass = BoardingPass::parse(&line.unwrap());
+        for pass in &self.passes {
             if pass.id > highest_id {
                 highest_id = pass.id;
             }
         }
-        println!("Day5.1: {}", highest_id);
-
-        Ok(())
+        Ok(highest_id)
     }
 
-    #[test]
-    fn day5_2() -> Result<()> {
+    fn part2(&self) -> Result<u16> {
         let mut lowest_id = 1000;
         let mut highest_id = 0;
 
         let mut passes = HashMap::new();
-        for line in read_file("day5.txt")? {
-            let pass = BoardingPass::parse(&line?);
+        for pass in &self.passes {
             if pass.id < lowest_id {
                 lowest_id = pass.id;
             }
@@ -114,10 +111,52 @@ mod tests {
                 && passes.contains_key(&(guess - 1))
                 && passes.contains_key(&(guess + 1))
             {
-                println!("Day5.2: {}", guess);
+                return Ok(guess);
             }
         }
 
+        bail!("No free seat found")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::answers::assert_answer;
+
+    #[test]
+    fn day5_smoke() -> Result<()> {
+        let pass = BoardingPass::parse("FBFBBFFRLR")?;
+        assert_eq!(44, pass.row);
+        assert_eq!(5, pass.col);
+        assert_eq!(357, pass.id);
+
+        let pass = BoardingPass::parse("BFFFBBFRRR")?;
+        assert_eq!(70, pass.row);
+        assert_eq!(7, pass.col);
+        assert_eq!(567, pass.id);
+        Ok(())
+    }
+
+    #[test]
+    fn day5_bad_passes() {
+        assert!(Day5::parse("FBFBBFFRL\n").is_err());
+        assert!(Day5::parse("FBFBBFFRLRR\n").is_err());
+        assert!(Day5::parse("FBFBBFRRLR\n").is_err());
+        assert!(Day5::parse("FBFBBFFRLB\n").is_err());
+    }
+
+    #[test]
+    fn day5_1() -> Result<()> {
+        assert_answer(5, 1, "day5.txt", Day5::load("day5.txt")?.part1()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn day5_2() -> Result<()> {
+        assert_answer(5, 2, "day5.txt", Day5::load("day5.txt")?.part2()?);
+
         Ok(())
     }
 }
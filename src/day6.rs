@@ -1,4 +1,4 @@
-use crate::read_file;
+use crate::{read_input, Solver};
 use anyhow::Result;
 use std::collections::HashMap;
 
@@ -8,11 +8,14 @@ struct Group {
 }
 
 fn read_yeses(file_name: &str) -> Result<Vec<Group>> {
+    Ok(parse_yeses(&read_input(file_name)?))
+}
+
+fn parse_yeses(input: &str) -> Vec<Group> {
     let mut result = vec![];
     let mut curr_group = HashMap::new();
     let mut size = 0;
-    for line in read_file(file_name)? {
-        let line = line?;
+    for line in input.lines() {
         let line = line.trim();
         if line.is_empty() && !curr_group.is_empty() {
             result.push(Group {
@@ -35,12 +38,44 @@ fn read_yeses(file_name: &str) -> Result<Vec<Group>> {
             size,
         });
     }
-    Ok(result)
+    result
+}
+
+pub struct Day6 {
+    groups: Vec<Group>,
+}
+
+impl Solver for Day6 {
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Day6> {
+        Ok(Day6 {
+            groups: parse_yeses(input),
+        })
+    }
+
+    fn part1(&self) -> Result<usize> {
+        Ok(self.groups.iter().map(|g| g.counts.len()).sum())
+    }
+
+    fn part2(&self) -> Result<usize> {
+        let mut count = 0;
+        for g in &self.groups {
+            for c in g.counts.values() {
+                if *c == g.size {
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::answers::assert_answer;
 
     #[test]
     fn day6_smoke() -> Result<()> {
@@ -66,28 +101,14 @@ mod tests {
 
     #[test]
     fn day6_1() -> Result<()> {
-        let groups = read_yeses("day6.txt")?;
-        let mut count = 0;
-        for g in groups {
-            count += g.counts.len();
-        }
-        println!("Day6.1: {}", count);
+        assert_answer(6, 1, "day6.txt", Day6::load("day6.txt")?.part1()?);
 
         Ok(())
     }
 
     #[test]
     fn day6_2() -> Result<()> {
-        let groups = read_yeses("day6.txt")?;
-        let mut count = 0;
-        for g in &groups {
-            for c in g.counts.values() {
-                if *c == g.size {
-                    count += 1;
-                }
-            }
-        }
-        println!("Day6.2: {}", count);
+        assert_answer(6, 2, "day6.txt", Day6::load("day6.txt")?.part2()?);
 
         Ok(())
     }
@@ -1,6 +1,6 @@
-use crate::read_file;
+use crate::{read_input, Solver};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::{collections::HashMap, vec};
@@ -17,7 +17,9 @@ impl Rule {
             static ref CONTAINER_RE: Regex = Regex::new(r"^(.*?) bags contain ").unwrap();
             static ref CONTENT_RE: Regex = Regex::new(r"(\d+) (.*?) bags?(?:,|\.)").unwrap();
         }
-        let outer = CONTAINER_RE.captures(line).unwrap();
+        let outer = CONTAINER_RE
+            .captures(line)
+            .with_context(|| format!("Invalid rule: {}", line))?;
         let color = outer.get(1).unwrap().as_str().to_string();
 
         let mut contents = HashMap::new();
@@ -32,10 +34,13 @@ impl Rule {
     }
 }
 
-fn parse_rules(file_name: &str) -> Result<HashMap<String, Rule>> {
+fn load_rules(file_name: &str) -> Result<HashMap<String, Rule>> {
+    parse_rules(&read_input(file_name)?)
+}
+
+fn parse_rules(input: &str) -> Result<HashMap<String, Rule>> {
     let mut result = HashMap::new();
-    for line in read_file(file_name)? {
-        let line = line?;
+    for line in input.lines() {
         let line = line.trim();
 
         let rule = Rule::parse(line)?;
@@ -80,28 +85,53 @@ fn find_containers(rules: &HashMap<String, Rule>, target: &str) -> Vec<String> {
     result
 }
 
-fn count_bags(rules: &HashMap<String, Rule>, target: &str) -> u32 {
+fn count_bags(rules: &HashMap<String, Rule>, target: &str) -> Result<u32> {
     let mut queue = vec![(target, 1)];
     let mut result = 0;
-    while !queue.is_empty() {
+    while let Some(current) = queue.pop() {
         // println!("Count {}, Current queue: {:?}", result, queue);
-        let current = queue.pop().unwrap();
         result += current.1;
-        let rule = rules.get(current.0).unwrap();
+        let rule = rules
+            .get(current.0)
+            .with_context(|| format!("No rule for {} bags", current.0))?;
         for e in &rule.contents {
             queue.push((e.0, e.1 * current.1));
         }
     }
-    result - 1
+    Ok(result - 1)
+}
+
+pub struct Day7 {
+    rules: HashMap<String, Rule>,
+}
+
+impl Solver for Day7 {
+    type Part1 = usize;
+    type Part2 = u32;
+
+    fn parse(input: &str) -> Result<Day7> {
+        Ok(Day7 {
+            rules: parse_rules(input)?,
+        })
+    }
+
+    fn part1(&self) -> Result<usize> {
+        Ok(find_containers(&self.rules, "shiny gold").len())
+    }
+
+    fn part2(&self) -> Result<u32> {
+        count_bags(&self.rules, "shiny gold")
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::answers::assert_answer;
 
     #[test]
     fn day7_smoke1() -> Result<()> {
-        let rules = parse_rules("day7_smoke.txt")?;
+        let rules = load_rules("day7_smoke.txt")?;
         // println!("Day7 smoke1 rules: {:?}", rules);
         let outermost = find_containers(&rules, "shiny gold");
         println!("Day7 smoke1: {:?}", outermost);
@@ -112,18 +142,15 @@ mod tests {
 
     #[test]
     fn day7_1() -> Result<()> {
-        let rules = parse_rules("day7.txt")?;
-        // println!("Day7  rules: {:?}", rules);
-        let outermost = find_containers(&rules, "shiny gold");
-        println!("Day7.1: {}", outermost.len());
+        assert_answer(7, 1, "day7.txt", Day7::load("day7.txt")?.part1()?);
 
         Ok(())
     }
 
     #[test]
     fn day7_smoke2() -> Result<()> {
-        let rules = parse_rules("day7_smoke2.txt")?;
-        let size = count_bags(&rules, "shiny gold");
+        let rules = load_rules("day7_smoke2.txt")?;
+        let size = count_bags(&rules, "shiny gold")?;
         assert_eq!(126, size);
 
         Ok(())
@@ -131,10 +158,16 @@ mod tests {
 
     #[test]
     fn day7_2() -> Result<()> {
-        let rules = parse_rules("day7.txt")?;
-        let size = count_bags(&rules, "shiny gold");
-        println!("Day7.2: {}", size);
+        assert_answer(7, 2, "day7.txt", Day7::load("day7.txt")?.part2()?);
 
         Ok(())
     }
+
+    #[test]
+    fn day7_missing_rule() -> Result<()> {
+        let e = Day7::parse("")?.part2().unwrap_err();
+        assert_eq!("No rule for shiny gold bags", e.to_string());
+        assert!(Day7::parse("shiny gold bags\n").is_err());
+        Ok(())
+    }
 }
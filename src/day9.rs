@@ -1,14 +1,17 @@
 use std::cmp::Ordering;
 use std::collections::{HashMap, VecDeque};
 
-use crate::read_file;
+use crate::{read_input, Solver};
 use anyhow::{bail, Context, Result};
 
 fn load_numbers(file_name: &str) -> Result<VecDeque<u64>> {
+    parse_numbers(&read_input(file_name)?)
+}
+
+fn parse_numbers(input: &str) -> Result<VecDeque<u64>> {
     let mut result = VecDeque::new();
 
-    for line in read_file(file_name)? {
-        let line = line?;
+    for line in input.lines() {
         let line = line.trim();
         result.push_back(line.parse()?);
     }
@@ -23,14 +26,21 @@ struct XmasState {
 }
 
 impl XmasState {
-    fn load(mut values: VecDeque<u64>, preamble_size: usize) -> XmasState {
+    fn load(mut values: VecDeque<u64>, preamble_size: usize) -> Result<XmasState> {
+        if values.len() <= preamble_size {
+            bail!(
+                "Need more than {} numbers, got {}",
+                preamble_size,
+                values.len()
+            );
+        }
         let future = values.split_off(preamble_size);
-        XmasState {
+        Ok(XmasState {
             preamble_size,
             past: VecDeque::new(),
             preamble: values,
             future,
-        }
+        })
     }
 
     fn is_valid(&self, next_value: u64) -> bool {
@@ -54,7 +64,7 @@ impl XmasState {
     }
 
     fn is_next_valid(&self) -> bool {
-        let next_value = self.future.get(0);
+        let next_value = self.future.front();
         if next_value.is_none() {
             return false;
         }
@@ -75,12 +85,12 @@ impl XmasState {
             self.step()?;
         }
 
-        Ok(*self.future.get(0).context("No more values")?)
+        Ok(*self.future.front().context("No more values")?)
     }
 }
 
 fn find_range_sum(values: &[u64], target: &u64) -> Result<(u64, u64)> {
-    for start in 0..(values.len() - 1) {
+    for start in 0..values.len().saturating_sub(1) {
         let mut min = u64::MAX;
         let mut max = 0;
         let mut running_sum = 0;
@@ -103,6 +113,32 @@ fn find_range_sum(values: &[u64], target: &u64) -> Result<(u64, u64)> {
     bail!("No range found");
 }
 
+pub struct Day9 {
+    values: VecDeque<u64>,
+}
+
+impl Solver for Day9 {
+    type Part1 = u64;
+    type Part2 = u64;
+
+    fn parse(input: &str) -> Result<Day9> {
+        Ok(Day9 {
+            values: parse_numbers(input)?,
+        })
+    }
+
+    fn part1(&self) -> Result<u64> {
+        XmasState::load(self.values.clone(), 25)?.find_first_invalid()
+    }
+
+    fn part2(&self) -> Result<u64> {
+        let first_invalid = self.part1()?;
+        let mut values = self.values.clone();
+        let (min, max) = find_range_sum(values.make_contiguous(), &first_invalid)?;
+        Ok(min + max)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,7 +146,7 @@ mod tests {
     #[test]
     fn day9_smoke1() -> Result<()> {
         let mut values = load_numbers("day9_smoke.txt")?;
-        let mut state = XmasState::load(values.clone(), 5);
+        let mut state = XmasState::load(values.clone(), 5)?;
         println!("Day9 smoke1 state {:?}", state);
         assert_eq!(127, state.find_first_invalid()?);
 
@@ -126,7 +162,7 @@ mod tests {
     #[test]
     fn day9_1() -> Result<()> {
         let mut values = load_numbers("day9.txt")?;
-        let mut state = XmasState::load(values.clone(), 25);
+        let mut state = XmasState::load(values.clone(), 25)?;
         let first_invalid = state.find_first_invalid()?;
         println!("Day9.1 {}", first_invalid);
         assert_eq!(29221323, first_invalid);
@@ -137,4 +173,16 @@ mod tests {
         assert_eq!(4389369, min + max);
         Ok(())
     }
+
+    #[test]
+    fn day9_short_input() -> Result<()> {
+        let day = Day9::parse("1\n2\n3")?;
+        assert_eq!(
+            "Need more than 25 numbers, got 3",
+            day.part1().unwrap_err().to_string()
+        );
+        assert!(day.part2().is_err());
+        assert!(find_range_sum(&[], &1).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,383 @@
+//! Step debugger for [`Program`], and a small command line on top of it.
+
+use std::{
+    collections::{BTreeSet, HashSet, VecDeque},
+    fmt::Display,
+    io::{BufRead, Write},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::handheld::{ExecutionOutcome, Program, Snapshot};
+
+/// Why the debugger stopped, or what a single step did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// One instruction ran and nothing interesting happened.
+    Stepped,
+    /// About to run the instruction at `pc`, which has a breakpoint.
+    Breakpoint { pc: usize },
+    /// The instruction at `pc` changed the watched accumulator.
+    AccumulatorChanged { pc: usize, old: i64, new: i64 },
+    /// The instruction at `pc` already ran once in the same state, after `steps` instructions.
+    LoopDetected { pc: usize, steps: usize },
+    /// The instruction at `pc` needs input.
+    WaitingForInput { pc: usize },
+    /// pc is just past the last instruction.
+    Terminated,
+    /// The jump at `pc` would go to `target`, outside the program.
+    JumpOutOfRange { pc: usize, target: i64 },
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::Stepped => write!(f, "stepped"),
+            Event::Breakpoint { pc } => write!(f, "breakpoint at pc {}", pc),
+            Event::AccumulatorChanged { pc, old, new } => {
+                write!(f, "accumulator {} -> {} at pc {}", old, new, pc)
+            }
+            Event::LoopDetected { pc, steps } => {
+                write!(f, "loop detected at pc {} after {} steps", pc, steps)
+            }
+            Event::WaitingForInput { pc } => write!(f, "waiting for input at pc {}", pc),
+            Event::Terminated => write!(f, "terminated"),
+            Event::JumpOutOfRange { pc, target } => {
+                write!(f, "jump from pc {} to {} leaves the program", pc, target)
+            }
+        }
+    }
+}
+
+pub struct Debugger {
+    initial: Program,
+    program: Program,
+    breakpoints: BTreeSet<usize>,
+    watch: bool,
+    branches: bool,
+    visited: HashSet<Snapshot>,
+    history: VecDeque<usize>,
+    history_len: usize,
+    steps: usize,
+}
+
+impl Debugger {
+    pub fn new(program: Program) -> Debugger {
+        Debugger {
+            initial: program.clone(),
+            breakpoints: BTreeSet::new(),
+            watch: false,
+            branches: program.branches(),
+            visited: HashSet::new(),
+            history: VecDeque::new(),
+            history_len: 16,
+            steps: 0,
+            program,
+        }
+    }
+
+    /// How many of the most recent pcs to keep for [`Debugger::backtrace`].
+    pub fn with_history(mut self, history_len: usize) -> Debugger {
+        self.history_len = history_len;
+        self
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Returns false if there already was a breakpoint at `pc`.
+    pub fn add_breakpoint(&mut self, pc: usize) -> bool {
+        self.breakpoints.insert(pc)
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: usize) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn set_watch(&mut self, watch: bool) {
+        self.watch = watch;
+    }
+
+    pub fn watching(&self) -> bool {
+        self.watch
+    }
+
+    /// The last executed pcs, oldest first.
+    pub fn backtrace(&self) -> impl Iterator<Item = usize> + '_ {
+        self.history.iter().copied()
+    }
+
+    /// Back to the program as it was loaded. Breakpoints and the watch stay.
+    pub fn reset(&mut self) {
+        self.program = self.initial.clone();
+        self.visited.clear();
+        self.history.clear();
+        self.steps = 0;
+    }
+
+    /// Runs one instruction, unless the program is over or about to repeat itself.
+    pub fn step(&mut self) -> Event {
+        let pc = self.program.pc;
+        let snapshot = self.program.snapshot(self.branches);
+        if self.visited.contains(&snapshot) {
+            return Event::LoopDetected {
+                pc,
+                steps: self.steps,
+            };
+        }
+
+        let old = self.program.accumulator();
+        match self.program.step() {
+            Some(ExecutionOutcome::JumpOutOfRange { pc, target }) => {
+                return Event::JumpOutOfRange { pc, target }
+            }
+            Some(ExecutionOutcome::WaitingForInput { pc }) => return Event::WaitingForInput { pc },
+            Some(_) => return Event::Terminated,
+            None => (),
+        }
+        self.visited.insert(snapshot);
+        self.steps += 1;
+        self.history.push_back(pc);
+        while self.history.len() > self.history_len {
+            self.history.pop_front();
+        }
+
+        let new = self.program.accumulator();
+        if self.watch && old != new {
+            Event::AccumulatorChanged { pc, old, new }
+        } else {
+            Event::Stepped
+        }
+    }
+
+    /// Steps until something other than a plain step happens, or the next pc has a breakpoint.
+    pub fn cont(&mut self) -> Event {
+        loop {
+            let event = self.step();
+            if event != Event::Stepped {
+                return event;
+            }
+            if self.breakpoints.contains(&self.program.pc) {
+                return Event::Breakpoint {
+                    pc: self.program.pc,
+                };
+            }
+        }
+    }
+}
+
+const HELP: &str = "Commands:
+    s, step [<n>]       run n instructions (default 1)
+    c, continue         run until a breakpoint, watch, loop or the end
+    b, break <pc>       add a breakpoint
+    d, delete <pc>      remove a breakpoint
+    w, watch            toggle stopping when the accumulator changes
+    bt, backtrace [<n>] show the last n executed pcs
+    p, print            show pc, accumulator and the next instruction
+    l, list             show the instructions around pc
+    r, reset            start the program again
+    q, quit";
+
+/// Reads commands from `input` until `quit` or the end of it, writing what happens to `output`.
+pub fn repl<R: BufRead, W: Write>(debugger: &mut Debugger, input: R, mut output: W) -> Result<()> {
+    write!(output, "(dbg) ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if let Some(&command) = words.first() {
+            if command == "q" || command == "quit" {
+                break;
+            }
+            if let Err(e) = command_line(debugger, command, &words[1..], &mut output) {
+                writeln!(output, "Error: {:#}", e)?;
+            }
+        }
+        write!(output, "(dbg) ")?;
+        output.flush()?;
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
+fn command_line<W: Write>(
+    debugger: &mut Debugger,
+    command: &str,
+    args: &[&str],
+    output: &mut W,
+) -> Result<()> {
+    let number = |default: Option<usize>| -> Result<usize> {
+        match args.first() {
+            Some(arg) => arg.parse().context("Expected a number"),
+            None => default.context("Expected a number"),
+        }
+    };
+
+    match command {
+        "s" | "step" => {
+            for _ in 0..number(Some(1))? {
+                let event = debugger.step();
+                if event != Event::Stepped {
+                    writeln!(output, "{}", event)?;
+                    break;
+                }
+            }
+            print_state(debugger, output)?;
+        }
+        "c" | "continue" => {
+            writeln!(output, "{}", debugger.cont())?;
+            print_state(debugger, output)?;
+        }
+        "b" | "break" => {
+            let pc = number(None)?;
+            if pc >= debugger.program().instructions.len() {
+                bail!("No instruction at pc {}", pc);
+            }
+            debugger.add_breakpoint(pc);
+            let breakpoints: Vec<String> = debugger.breakpoints().map(|b| b.to_string()).collect();
+            writeln!(output, "breakpoints: {}", breakpoints.join(", "))?;
+        }
+        "d" | "delete" => {
+            let pc = number(None)?;
+            if !debugger.remove_breakpoint(pc) {
+                bail!("No breakpoint at pc {}", pc);
+            }
+        }
+        "w" | "watch" => {
+            debugger.set_watch(!debugger.watching());
+            let state = if debugger.watching() { "on" } else { "off" };
+            writeln!(output, "watch {}", state)?;
+        }
+        "bt" | "backtrace" => {
+            let pcs: Vec<usize> = debugger.backtrace().collect();
+            let n = number(Some(pcs.len()))?.min(pcs.len());
+            for pc in &pcs[pcs.len() - n..] {
+                writeln!(
+                    output,
+                    "{:>5}  {}",
+                    pc,
+                    debugger.program().instructions[*pc]
+                )?;
+            }
+        }
+        "p" | "print" => print_state(debugger, output)?,
+        "l" | "list" => {
+            let program = debugger.program();
+            let from = program.pc.saturating_sub(3);
+            let to = (program.pc + 4).min(program.instructions.len());
+            for pc in from..to {
+                let marker = if pc == program.pc { '>' } else { ' ' };
+                writeln!(output, "{} {:>5}  {}", marker, pc, program.instructions[pc])?;
+            }
+        }
+        "r" | "reset" => {
+            debugger.reset();
+            print_state(debugger, output)?;
+        }
+        "h" | "help" => writeln!(output, "{}", HELP)?,
+        _ => bail!("Unknown command {}, try help", command),
+    }
+    Ok(())
+}
+
+fn print_state<W: Write>(debugger: &Debugger, output: &mut W) -> Result<()> {
+    let program = debugger.program();
+    write!(
+        output,
+        "pc {} acc {} steps {}",
+        program.pc,
+        program.accumulator(),
+        debugger.steps()
+    )?;
+    match program.instructions.get(program.pc) {
+        Some(instruction) => writeln!(output, "  next: {}", instruction)?,
+        None => writeln!(output)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn smoke() -> Result<Debugger> {
+        Ok(Debugger::new(Program::load_program("day8_smoke.txt")?))
+    }
+
+    #[test]
+    fn loop_report() -> Result<()> {
+        let mut debugger = smoke()?;
+        let event = debugger.cont();
+        assert_eq!(Event::LoopDetected { pc: 1, steps: 7 }, event);
+        assert_eq!("loop detected at pc 1 after 7 steps", event.to_string());
+        assert_eq!(5, debugger.program().accumulator());
+        Ok(())
+    }
+
+    #[test]
+    fn breakpoints_and_watch() -> Result<()> {
+        let mut debugger = smoke()?.with_history(3);
+        assert!(debugger.add_breakpoint(3));
+        assert!(!debugger.add_breakpoint(3));
+        assert_eq!(Event::Breakpoint { pc: 3 }, debugger.cont());
+        assert_eq!(vec![2, 6, 7], debugger.backtrace().collect::<Vec<_>>());
+
+        debugger.set_watch(true);
+        assert_eq!(
+            Event::AccumulatorChanged {
+                pc: 3,
+                old: 2,
+                new: 5
+            },
+            debugger.step()
+        );
+        assert!(debugger.remove_breakpoint(3));
+        assert_eq!(Event::Stepped, debugger.step());
+        assert_eq!(vec![7, 3, 4], debugger.backtrace().collect::<Vec<_>>());
+
+        debugger.reset();
+        assert_eq!(0, debugger.program().pc);
+        assert_eq!(0, debugger.steps());
+        assert_eq!(0, debugger.backtrace().count());
+        Ok(())
+    }
+
+    #[test]
+    fn terminate_and_fault() -> Result<()> {
+        let mut debugger = Debugger::new(Program::parse("acc +2\nnop +0\n")?);
+        assert_eq!(Event::Terminated, debugger.cont());
+        assert_eq!(2, debugger.program().accumulator());
+
+        let mut debugger = Debugger::new(Program::parse("jmp +5\n")?);
+        assert_eq!(Event::JumpOutOfRange { pc: 0, target: 5 }, debugger.cont());
+        assert_eq!(0, debugger.program().pc);
+        Ok(())
+    }
+
+    #[test]
+    fn repl_session() -> Result<()> {
+        let mut debugger = smoke()?;
+        let commands = "break 4\nc\nbt 2\nstep 100\nbogus\nq\nstep\n";
+        let mut output = vec![];
+        repl(&mut debugger, commands.as_bytes(), &mut output)?;
+        let output = String::from_utf8(output)?;
+
+        assert!(output.contains("breakpoints: 4"));
+        assert!(output.contains("breakpoint at pc 4"));
+        assert!(output.contains("    3  acc +3\n"));
+        assert!(output.contains("loop detected at pc 1 after 7 steps"));
+        assert!(output.contains("Error: Unknown command bogus"));
+        // Nothing runs after quit.
+        assert_eq!(7, debugger.steps());
+        Ok(())
+    }
+}
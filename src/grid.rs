@@ -0,0 +1,568 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Error, Result};
+
+/// `(x, y)` with `y` growing downwards, as in the puzzle inputs.
+pub type Point = (i32, i32);
+
+pub fn add(a: Point, b: Point) -> Point {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+/// A cell which can be read from and drawn as a single character of a char map.
+pub trait Cell: Sized {
+    fn from_char(c: char) -> Result<Self>;
+    fn to_char(&self) -> char;
+}
+
+impl Cell for char {
+    fn from_char(c: char) -> Result<char> {
+        Ok(c)
+    }
+
+    fn to_char(&self) -> char {
+        *self
+    }
+}
+
+/// `#` is set, `.` is clear.
+impl Cell for bool {
+    fn from_char(c: char) -> Result<bool> {
+        match c {
+            '#' => Ok(true),
+            '.' => Ok(false),
+            _ => bail!("Invalid character {:?}", c),
+        }
+    }
+
+    fn to_char(&self) -> char {
+        if *self {
+            '#'
+        } else {
+            '.'
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// The 4 cells sharing an edge.
+    Orthogonal,
+    /// The 8 cells sharing an edge or a corner.
+    Moore,
+    Custom(&'static [Point]),
+}
+
+const ORTHOGONAL: [Point; 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+const MOORE: [Point; 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+impl Neighborhood {
+    pub fn offsets(&self) -> &'static [Point] {
+        match self {
+            Neighborhood::Orthogonal => &ORTHOGONAL,
+            Neighborhood::Moore => &MOORE,
+            Neighborhood::Custom(offsets) => offsets,
+        }
+    }
+
+    pub fn around(&self, p: Point) -> impl Iterator<Item = Point> {
+        self.offsets().iter().map(move |o| add(p, *o))
+    }
+}
+
+/// What a dense grid does with coordinates past one of its edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Edge {
+    /// Nothing is there.
+    Bounded,
+    /// The grid repeats forever.
+    Wrap,
+    /// The outermost cell repeats forever.
+    Clamp,
+}
+
+impl Edge {
+    fn resolve(&self, v: i32, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        let len = len as i32;
+        match self {
+            Edge::Bounded if v < 0 || v >= len => None,
+            Edge::Bounded => Some(v as usize),
+            Edge::Wrap => Some(v.rem_euclid(len) as usize),
+            Edge::Clamp => Some(v.clamp(0, len - 1) as usize),
+        }
+    }
+}
+
+/// Rectangular grid stored row by row.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    width: usize,
+    height: usize,
+    edges: (Edge, Edge),
+}
+
+impl<T> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Grid<T>
+    where
+        T: Clone,
+    {
+        Grid {
+            cells: vec![fill; width * height],
+            width,
+            height,
+            edges: (Edge::Bounded, Edge::Bounded),
+        }
+    }
+
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Result<Grid<T>> {
+        let width = rows.first().map_or(0, |r| r.len());
+        let height = rows.len();
+        let mut cells = Vec::with_capacity(width * height);
+        for (y, row) in rows.into_iter().enumerate() {
+            if row.len() != width {
+                bail!("Row {} has {} cells, expected {}", y, row.len(), width);
+            }
+            cells.extend(row);
+        }
+        Ok(Grid {
+            cells,
+            width,
+            height,
+            edges: (Edge::Bounded, Edge::Bounded),
+        })
+    }
+
+    /// Reads a char map, one row per line. Blank lines are skipped and every row must be the same length.
+    pub fn parse_with<F: FnMut(char) -> Result<T>>(input: &str, mut f: F) -> Result<Grid<T>> {
+        let mut rows = vec![];
+        for (idx, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let row = line
+                .chars()
+                .map(&mut f)
+                .collect::<Result<Vec<T>>>()
+                .with_context(|| format!("Invalid line {}", idx + 1))?;
+            rows.push(row);
+        }
+        Grid::from_rows(rows)
+    }
+
+    /// Changes how coordinates past the left/right (`x`) and top/bottom (`y`) edges are treated.
+    pub fn with_edges(mut self, x: Edge, y: Edge) -> Grid<T> {
+        self.edges = (x, y);
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, p: Point) -> Option<usize> {
+        let x = self.edges.0.resolve(p.0, self.width)?;
+        let y = self.edges.1.resolve(p.1, self.height)?;
+        Some(y * self.width + x)
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        p.0 >= 0 && p.1 >= 0 && (p.0 as usize) < self.width && (p.1 as usize) < self.height
+    }
+
+    pub fn get(&self, p: Point) -> Option<&T> {
+        self.index(p).map(|i| &self.cells[i])
+    }
+
+    pub fn get_mut(&mut self, p: Point) -> Option<&mut T> {
+        let i = self.index(p)?;
+        Some(&mut self.cells[i])
+    }
+
+    pub fn set(&mut self, p: Point, value: T) -> Result<()> {
+        match self.get_mut(p) {
+            Some(cell) => *cell = value,
+            None => bail!("{:?} is outside the grid", p),
+        }
+        Ok(())
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        let width = self.width as i32;
+        (0..self.height as i32).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> {
+        self.points().zip(self.cells.iter())
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    pub fn row(&self, y: usize) -> impl Iterator<Item = &T> {
+        self.cells[y * self.width..(y + 1) * self.width].iter()
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        self.cells.iter().skip(x).step_by(self.width.max(1))
+    }
+
+    pub fn count<F: Fn(&T) -> bool>(&self, f: F) -> usize {
+        self.cells.iter().filter(|c| f(c)).count()
+    }
+
+    /// Neighbors of `p` which exist under this grid's edge rules.
+    pub fn neighbors(
+        &self,
+        p: Point,
+        neighborhood: Neighborhood,
+    ) -> impl Iterator<Item = (Point, &T)> + '_ {
+        neighborhood
+            .around(p)
+            .filter_map(move |n| self.get(n).map(|v| (n, v)))
+    }
+
+    /// Cells seen walking from `from` (exclusive) by `step` until leaving the grid. Ignores wrapping.
+    pub fn ray(&self, from: Point, step: Point) -> impl Iterator<Item = (Point, &T)> + '_ {
+        let mut p = from;
+        std::iter::from_fn(move || {
+            p = add(p, step);
+            if self.contains(p) {
+                Some((p, &self.cells[p.1 as usize * self.width + p.0 as usize]))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            cells: self.cells.iter().map(f).collect(),
+            width: self.width,
+            height: self.height,
+            edges: self.edges,
+        }
+    }
+
+    /// Like [`Grid::map`], but `f` is also told where each cell is.
+    pub fn map_points<U, F: FnMut(Point, &T) -> U>(&self, mut f: F) -> Grid<U> {
+        Grid {
+            cells: self.iter().map(|(p, v)| f(p, v)).collect(),
+            width: self.width,
+            height: self.height,
+            edges: self.edges,
+        }
+    }
+
+    fn remap<F: Fn(Point) -> Point>(&self, width: usize, height: usize, source: F) -> Grid<T>
+    where
+        T: Clone,
+    {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let (sx, sy) = source((x, y));
+                cells.push(self.cells[sy as usize * self.width + sx as usize].clone());
+            }
+        }
+        Grid {
+            cells,
+            width,
+            height,
+            edges: self.edges,
+        }
+    }
+
+    /// A quarter turn clockwise.
+    pub fn rotate_cw(&self) -> Grid<T>
+    where
+        T: Clone,
+    {
+        let h = self.height as i32;
+        self.remap(self.height, self.width, |(x, y)| (y, h - 1 - x))
+    }
+
+    /// Mirrors left to right.
+    pub fn flip_horizontal(&self) -> Grid<T>
+    where
+        T: Clone,
+    {
+        let w = self.width as i32;
+        self.remap(self.width, self.height, |(x, y)| (w - 1 - x, y))
+    }
+
+    /// Mirrors top to bottom.
+    pub fn flip_vertical(&self) -> Grid<T>
+    where
+        T: Clone,
+    {
+        let h = self.height as i32;
+        self.remap(self.width, self.height, |(x, y)| (x, h - 1 - y))
+    }
+
+    /// All 8 rotations and reflections: the four clockwise turns of the grid, then of its mirror image.
+    pub fn orientations(&self) -> Vec<Grid<T>>
+    where
+        T: Clone,
+    {
+        let mut result = Vec::with_capacity(8);
+        for start in [self.clone(), self.flip_horizontal()].iter() {
+            let mut g = start.clone();
+            for _ in 0..4 {
+                let next = g.rotate_cw();
+                result.push(g);
+                g = next;
+            }
+        }
+        result
+    }
+
+    pub fn render<F: Fn(&T) -> char>(&self, f: F) -> String {
+        let mut result = String::with_capacity((self.width + 1) * self.height);
+        for y in 0..self.height {
+            result.extend(self.row(y).map(&f));
+            result.push('\n');
+        }
+        result
+    }
+}
+
+impl<T: Cell> FromStr for Grid<T> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Grid<T>> {
+        Grid::parse_with(s, T::from_char)
+    }
+}
+
+impl<T: Cell> Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(T::to_char))
+    }
+}
+
+/// Unbounded grid holding only the cells which have been set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseGrid<T> {
+    cells: HashMap<Point, T>,
+}
+
+impl<T> Default for SparseGrid<T> {
+    fn default() -> Self {
+        SparseGrid {
+            cells: HashMap::new(),
+        }
+    }
+}
+
+impl<T> SparseGrid<T> {
+    pub fn new() -> SparseGrid<T> {
+        SparseGrid::default()
+    }
+
+    /// Reads a char map with its top left corner at the origin, keeping the cells `f` returns.
+    pub fn parse_with<F: FnMut(char) -> Result<Option<T>>>(
+        input: &str,
+        mut f: F,
+    ) -> Result<SparseGrid<T>> {
+        let mut cells = HashMap::new();
+        for (y, line) in input.lines().enumerate() {
+            for (x, c) in line.trim().chars().enumerate() {
+                let value = f(c).with_context(|| format!("Invalid line {}", y + 1))?;
+                if let Some(value) = value {
+                    cells.insert((x as i32, y as i32), value);
+                }
+            }
+        }
+        Ok(SparseGrid { cells })
+    }
+
+    pub fn get(&self, p: Point) -> Option<&T> {
+        self.cells.get(&p)
+    }
+
+    pub fn get_mut(&mut self, p: Point) -> Option<&mut T> {
+        self.cells.get_mut(&p)
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        self.cells.contains_key(&p)
+    }
+
+    pub fn insert(&mut self, p: Point, value: T) -> Option<T> {
+        self.cells.insert(p, value)
+    }
+
+    pub fn remove(&mut self, p: Point) -> Option<T> {
+        self.cells.remove(&p)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> {
+        self.cells.iter().map(|(p, v)| (*p, v))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.cells.values()
+    }
+
+    /// Smallest and largest corner (inclusive) of the set cells.
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        let mut points = self.cells.keys();
+        let first = *points.next()?;
+        Some(points.fold((first, first), |(min, max), p| {
+            (
+                (min.0.min(p.0), min.1.min(p.1)),
+                (max.0.max(p.0), max.1.max(p.1)),
+            )
+        }))
+    }
+
+    /// Set neighbors of `p`.
+    pub fn neighbors(
+        &self,
+        p: Point,
+        neighborhood: Neighborhood,
+    ) -> impl Iterator<Item = (Point, &T)> + '_ {
+        neighborhood
+            .around(p)
+            .filter_map(move |n| self.get(n).map(|v| (n, v)))
+    }
+
+    /// Draws the bounding box of the set cells, using `empty` for the gaps.
+    pub fn render<F: Fn(&T) -> char>(&self, empty: char, f: F) -> String {
+        match self.bounds() {
+            Some((min, max)) => render(min, max, |p| self.get(p).map_or(empty, &f)),
+            None => String::new(),
+        }
+    }
+}
+
+impl<T: Cell> Display for SparseGrid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render('.', T::to_char))
+    }
+}
+
+/// Draws every point from `min` to `max` inclusive, one row per line.
+pub fn render<F: Fn(Point) -> char>(min: Point, max: Point, f: F) -> String {
+    let mut result = String::new();
+    for y in min.1..=max.1 {
+        result.extend((min.0..=max.0).map(|x| f((x, y))));
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "#..\n.#.\n##.\n#..\n";
+
+    #[test]
+    fn parse_and_render() -> Result<()> {
+        let grid: Grid<bool> = MAP.parse()?;
+        assert_eq!(3, grid.width());
+        assert_eq!(4, grid.height());
+        assert_eq!(Some(&true), grid.get((1, 2)));
+        assert_eq!(None, grid.get((3, 0)));
+        assert_eq!(5, grid.count(|c| *c));
+        assert_eq!(MAP, grid.to_string());
+
+        assert!("#.\n#..\n".parse::<Grid<bool>>().is_err());
+        assert!("#.\n#x\n".parse::<Grid<bool>>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn edges() -> Result<()> {
+        let grid: Grid<char> = "ab\ncd\n".parse()?;
+        let wrapped = grid.clone().with_edges(Edge::Wrap, Edge::Bounded);
+        assert_eq!(Some(&'b'), wrapped.get((-1, 0)));
+        assert_eq!(Some(&'c'), wrapped.get((4, 1)));
+        assert_eq!(None, wrapped.get((0, 2)));
+
+        let clamped = grid.with_edges(Edge::Clamp, Edge::Clamp);
+        assert_eq!(Some(&'a'), clamped.get((-5, -5)));
+        assert_eq!(Some(&'d'), clamped.get((9, 9)));
+        Ok(())
+    }
+
+    #[test]
+    fn neighbors() -> Result<()> {
+        let grid: Grid<bool> = MAP.parse()?;
+        assert_eq!(3, grid.neighbors((0, 0), Neighborhood::Moore).count());
+        assert_eq!(8, grid.neighbors((1, 1), Neighborhood::Moore).count());
+        assert_eq!(4, grid.neighbors((1, 1), Neighborhood::Orthogonal).count());
+        let set = grid
+            .neighbors((1, 1), Neighborhood::Moore)
+            .filter(|(_, c)| **c)
+            .count();
+        assert_eq!(3, set);
+
+        let ray: Vec<Point> = grid.ray((0, 0), (1, 1)).map(|(p, _)| p).collect();
+        assert_eq!(vec![(1, 1), (2, 2)], ray);
+        Ok(())
+    }
+
+    #[test]
+    fn transforms() -> Result<()> {
+        let grid: Grid<char> = "abc\ndef\n".parse()?;
+        assert_eq!("da\neb\nfc\n", grid.rotate_cw().to_string());
+        assert_eq!("cba\nfed\n", grid.flip_horizontal().to_string());
+        assert_eq!("def\nabc\n", grid.flip_vertical().to_string());
+        assert_eq!(grid, grid.rotate_cw().rotate_cw().rotate_cw().rotate_cw());
+
+        let orientations = grid.orientations();
+        assert_eq!(8, orientations.len());
+        let mut distinct: Vec<String> = orientations.iter().map(|g| g.to_string()).collect();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(8, distinct.len());
+        Ok(())
+    }
+
+    #[test]
+    fn sparse() -> Result<()> {
+        let mut grid =
+            SparseGrid::parse_with(MAP, |c| Ok(if c == '#' { Some(true) } else { None }))?;
+        assert_eq!(5, grid.len());
+        assert_eq!(Some(((0, 0), (1, 3))), grid.bounds());
+        assert_eq!(3, grid.neighbors((0, 1), Neighborhood::Orthogonal).count());
+
+        grid.insert((-2, 5), true);
+        assert_eq!(Some(((-2, 0), (1, 5))), grid.bounds());
+        assert_eq!("..#.\n...#\n..##\n..#.\n....\n#...\n", grid.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,1108 @@
+//! The handheld game console from day 8: its instruction set, an assembler and the machine itself.
+//!
+//! The console runs more than the puzzle's `nop`, `acc` and `jmp`: it has named registers, input and
+//! output queues, and any instruction implementing [`OpCode`] can be added to an [`InstructionSet`].
+
+use crate::read_input;
+use anyhow::{bail, Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt,
+    sync::Arc,
+};
+
+/// The register `acc` adds to.
+pub const ACCUMULATOR: &str = "acc";
+
+/// What an operand of an instruction has to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    /// A register the instruction writes to.
+    Register,
+    /// A number, constant, label or register the instruction reads.
+    Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Value(i64),
+    Register(String),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Value(v) => write!(f, "{:+}", v),
+            Operand::Register(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Everything an instruction can change apart from pc.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Machine {
+    pub registers: BTreeMap<String, i64>,
+    pub input: VecDeque<i64>,
+    pub output: VecDeque<i64>,
+}
+
+impl Machine {
+    /// Registers start out as 0.
+    pub fn get(&self, operand: &Operand) -> i64 {
+        match operand {
+            Operand::Value(v) => *v,
+            Operand::Register(name) => self.registers.get(name).copied().unwrap_or(0),
+        }
+    }
+
+    pub fn set(&mut self, operand: &Operand, value: i64) {
+        if let Operand::Register(name) = operand {
+            self.registers.insert(name.clone(), value);
+        }
+    }
+}
+
+/// What pc does after an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    Next,
+    /// Move pc by this much.
+    Jump(i64),
+    /// Nothing to read from the input queue yet; try the same instruction again later.
+    Wait,
+}
+
+/// Where an instruction can send pc, as far as can be told without running it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Next,
+    /// Always moves pc by this much.
+    Jump(i64),
+    /// Either moves pc by this much or goes on to the next instruction.
+    Branch(i64),
+    /// Moves pc by a register, so could go anywhere, or to the next instruction.
+    Dynamic,
+}
+
+/// One instruction of the console. [`InstructionSet::register`] makes new ones available to the assembler.
+pub trait OpCode: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn operands(&self) -> &'static [OperandKind];
+
+    /// `args` has one operand for each of [`OpCode::operands`].
+    fn execute(&self, args: &[Operand], machine: &mut Machine) -> Effect;
+
+    /// Which operand, if any, is the offset the instruction jumps by.
+    fn jump_operand(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether where pc goes next depends on the machine, like a conditional jump or waiting for input.
+    fn conditional(&self) -> bool {
+        false
+    }
+}
+
+const VALUE: [OperandKind; 1] = [OperandKind::Value];
+const REGISTER_VALUE: [OperandKind; 2] = [OperandKind::Register, OperandKind::Value];
+const VALUE_VALUE: [OperandKind; 2] = [OperandKind::Value, OperandKind::Value];
+const REGISTER: [OperandKind; 1] = [OperandKind::Register];
+
+/// `nop n` does nothing; `n` only matters to whoever patches it into a `jmp`.
+struct Nop;
+
+impl OpCode for Nop {
+    fn name(&self) -> &'static str {
+        "nop"
+    }
+
+    fn operands(&self) -> &'static [OperandKind] {
+        &VALUE
+    }
+
+    fn execute(&self, _args: &[Operand], _machine: &mut Machine) -> Effect {
+        Effect::Next
+    }
+}
+
+/// `acc n` adds `n` to the accumulator.
+struct Acc;
+
+impl OpCode for Acc {
+    fn name(&self) -> &'static str {
+        "acc"
+    }
+
+    fn operands(&self) -> &'static [OperandKind] {
+        &VALUE
+    }
+
+    fn execute(&self, args: &[Operand], machine: &mut Machine) -> Effect {
+        let acc = Operand::Register(ACCUMULATOR.to_owned());
+        machine.set(&acc, machine.get(&acc).wrapping_add(machine.get(&args[0])));
+        Effect::Next
+    }
+}
+
+/// `jmp n` moves pc by `n`.
+struct Jmp;
+
+impl OpCode for Jmp {
+    fn name(&self) -> &'static str {
+        "jmp"
+    }
+
+    fn operands(&self) -> &'static [OperandKind] {
+        &VALUE
+    }
+
+    fn execute(&self, args: &[Operand], machine: &mut Machine) -> Effect {
+        Effect::Jump(machine.get(&args[0]))
+    }
+
+    fn jump_operand(&self) -> Option<usize> {
+        Some(0)
+    }
+}
+
+/// `op x y` stores `f(x, y)` in register `x`. Arithmetic wraps.
+struct Arithmetic {
+    name: &'static str,
+    f: fn(i64, i64) -> i64,
+}
+
+impl OpCode for Arithmetic {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn operands(&self) -> &'static [OperandKind] {
+        &REGISTER_VALUE
+    }
+
+    fn execute(&self, args: &[Operand], machine: &mut Machine) -> Effect {
+        let value = (self.f)(machine.get(&args[0]), machine.get(&args[1]));
+        machine.set(&args[0], value);
+        Effect::Next
+    }
+}
+
+/// `op x y` moves pc by `y` when `test(x)` holds.
+struct ConditionalJump {
+    name: &'static str,
+    test: fn(i64) -> bool,
+}
+
+impl OpCode for ConditionalJump {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn operands(&self) -> &'static [OperandKind] {
+        &VALUE_VALUE
+    }
+
+    fn execute(&self, args: &[Operand], machine: &mut Machine) -> Effect {
+        if (self.test)(machine.get(&args[0])) {
+            Effect::Jump(machine.get(&args[1]))
+        } else {
+            Effect::Next
+        }
+    }
+
+    fn jump_operand(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn conditional(&self) -> bool {
+        true
+    }
+}
+
+/// `in x` takes the next input into register `x`, waiting for one if there isn't any.
+struct In;
+
+impl OpCode for In {
+    fn name(&self) -> &'static str {
+        "in"
+    }
+
+    fn operands(&self) -> &'static [OperandKind] {
+        &REGISTER
+    }
+
+    fn execute(&self, args: &[Operand], machine: &mut Machine) -> Effect {
+        match machine.input.pop_front() {
+            Some(value) => {
+                machine.set(&args[0], value);
+                Effect::Next
+            }
+            None => Effect::Wait,
+        }
+    }
+
+    fn conditional(&self) -> bool {
+        true
+    }
+}
+
+/// `out x` adds `x` to the output.
+struct Out;
+
+impl OpCode for Out {
+    fn name(&self) -> &'static str {
+        "out"
+    }
+
+    fn operands(&self) -> &'static [OperandKind] {
+        &VALUE
+    }
+
+    fn execute(&self, args: &[Operand], machine: &mut Machine) -> Effect {
+        let value = machine.get(&args[0]);
+        machine.output.push_back(value);
+        Effect::Next
+    }
+}
+
+/// The instructions the assembler knows, by name.
+#[derive(Clone)]
+pub struct InstructionSet {
+    ops: HashMap<&'static str, Arc<dyn OpCode>>,
+}
+
+lazy_static! {
+    static ref STANDARD: InstructionSet = InstructionSet::standard();
+}
+
+impl InstructionSet {
+    /// Just `nop`, `acc` and `jmp`.
+    pub fn day8() -> InstructionSet {
+        let mut isa = InstructionSet {
+            ops: HashMap::new(),
+        };
+        isa.register(Nop);
+        isa.register(Acc);
+        isa.register(Jmp);
+        isa
+    }
+
+    /// Day 8's instructions plus `set`, `add`, `mul`, `jnz`, `jgz`, `in` and `out`.
+    pub fn standard() -> InstructionSet {
+        let mut isa = InstructionSet::day8();
+        isa.register(Arithmetic {
+            name: "set",
+            f: |_, y| y,
+        });
+        isa.register(Arithmetic {
+            name: "add",
+            f: i64::wrapping_add,
+        });
+        isa.register(Arithmetic {
+            name: "mul",
+            f: i64::wrapping_mul,
+        });
+        isa.register(ConditionalJump {
+            name: "jnz",
+            test: |x| x != 0,
+        });
+        isa.register(ConditionalJump {
+            name: "jgz",
+            test: |x| x > 0,
+        });
+        isa.register(In);
+        isa.register(Out);
+        isa
+    }
+
+    /// Adds `op`, replacing any instruction of the same name.
+    pub fn register<O: OpCode + 'static>(&mut self, op: O) -> &mut InstructionSet {
+        self.ops.insert(op.name(), Arc::new(op));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn OpCode>> {
+        self.ops.get(name).cloned()
+    }
+
+    /// The instruction called `name` from [`InstructionSet::standard`].
+    pub fn builtin(name: &str) -> Option<Arc<dyn OpCode>> {
+        STANDARD.get(name)
+    }
+}
+
+#[derive(Clone)]
+pub struct Instruction {
+    pub op: Arc<dyn OpCode>,
+    pub args: Vec<Operand>,
+}
+
+impl Instruction {
+    /// Where this can send pc when it runs at `pc`.
+    pub fn flow(&self) -> Flow {
+        let offset = match self.op.jump_operand() {
+            Some(idx) => &self.args[idx],
+            None => return Flow::Next,
+        };
+        match offset {
+            Operand::Value(v) if self.op.conditional() => Flow::Branch(*v),
+            Operand::Value(v) => Flow::Jump(*v),
+            Operand::Register(_) => Flow::Dynamic,
+        }
+    }
+}
+
+impl PartialEq for Instruction {
+    fn eq(&self, other: &Instruction) -> bool {
+        self.op.name() == other.op.name() && self.args == other.args
+    }
+}
+
+impl Eq for Instruction {}
+
+impl fmt::Debug for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Instruction({})", self)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.op.name())?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Turns assembly into instructions from `isa`. On top of the puzzle's `op +n` lines this understands:
+///
+/// - comments from `;` or `#` to the end of the line
+/// - labels, `loop:` on a line of their own or before an instruction
+/// - constants, `.const STEP -3`
+/// - registers, `.reg a b`. Any name an instruction writes to is a register too, as is `acc`.
+/// - a label, constant or register name in place of a number. Labels become the offset from the
+///   instruction to the label.
+pub fn assemble(input: &str, isa: &InstructionSet) -> Result<Vec<Instruction>> {
+    lazy_static! {
+        static ref LABEL_RE: Regex = Regex::new(r"^([A-Za-z_][A-Za-z0-9_]*):\s*(.*)$").unwrap();
+        static ref CONST_RE: Regex =
+            Regex::new(r"^\.const\s+([A-Za-z_][A-Za-z0-9_]*)\s+(\S+)$").unwrap();
+        static ref REG_RE: Regex = Regex::new(r"^\.reg((?:\s+[A-Za-z_][A-Za-z0-9_]*)+)$").unwrap();
+        static ref OP_RE: Regex = Regex::new(r"^([a-z]+)((?:\s+\S+)*)$").unwrap();
+        static ref NAME_RE: Regex = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+    }
+
+    // First pass: find every label, constant and register, and where each instruction comes from.
+    let mut symbols: HashMap<&str, Symbol> = HashMap::new();
+    symbols.insert(ACCUMULATOR, Symbol::Register);
+    let mut lines = vec![];
+    for (idx, line) in input.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut text = line.split([';', '#']).next().unwrap().trim();
+
+        while let Some(c) = LABEL_RE.captures(text) {
+            let name = c.get(1).unwrap().as_str();
+            define(&mut symbols, name, Symbol::Label(lines.len()), line_no)?;
+            text = c.get(2).unwrap().as_str();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        if let Some(c) = CONST_RE.captures(text) {
+            let name = c.get(1).unwrap().as_str();
+            let value = c
+                .get(2)
+                .unwrap()
+                .as_str()
+                .parse()
+                .with_context(|| format!("Invalid constant on line {}", line_no))?;
+            define(&mut symbols, name, Symbol::Const(value), line_no)?;
+            continue;
+        }
+        if let Some(c) = REG_RE.captures(text) {
+            for name in c.get(1).unwrap().as_str().split_whitespace() {
+                define(&mut symbols, name, Symbol::Register, line_no)?;
+            }
+            continue;
+        }
+
+        let c = OP_RE
+            .captures(text)
+            .with_context(|| format!("Invalid instruction on line {}: {}", line_no, text))?;
+        let name = c.get(1).unwrap().as_str();
+        let op = isa
+            .get(name)
+            .with_context(|| format!("Unknown instruction on line {}: {}", line_no, name))?;
+        let args: Vec<&str> = c.get(2).unwrap().as_str().split_whitespace().collect();
+        if args.len() != op.operands().len() {
+            bail!(
+                "{} takes {} operands, not {}, on line {}",
+                name,
+                op.operands().len(),
+                args.len(),
+                line_no
+            );
+        }
+        for (arg, kind) in args.iter().zip(op.operands()) {
+            if *kind == OperandKind::Register {
+                if !NAME_RE.is_match(arg) {
+                    bail!("Expected a register on line {}: {}", line_no, arg);
+                }
+                define(&mut symbols, arg, Symbol::Register, line_no)?;
+            }
+        }
+        lines.push((line_no, op, args));
+    }
+
+    // Second pass: resolve the arguments now that every name is known.
+    let mut instructions = vec![];
+    for (pc, (line_no, op, args)) in lines.into_iter().enumerate() {
+        let args = args
+            .into_iter()
+            .map(|arg| {
+                if NAME_RE.is_match(arg) {
+                    Ok(match symbols.get(arg) {
+                        Some(Symbol::Label(target)) => Operand::Value(*target as i64 - pc as i64),
+                        Some(Symbol::Const(value)) => Operand::Value(*value),
+                        Some(Symbol::Register) => Operand::Register(arg.to_owned()),
+                        None => bail!("Unknown name on line {}: {}", line_no, arg),
+                    })
+                } else {
+                    Ok(Operand::Value(arg.parse().with_context(|| {
+                        format!("Invalid argument on line {}: {}", line_no, arg)
+                    })?))
+                }
+            })
+            .collect::<Result<_>>()?;
+        instructions.push(Instruction { op, args });
+    }
+    Ok(instructions)
+}
+
+enum Symbol {
+    Label(usize),
+    Const(i64),
+    Register,
+}
+
+fn define<'a>(
+    symbols: &mut HashMap<&'a str, Symbol>,
+    name: &'a str,
+    symbol: Symbol,
+    line_no: usize,
+) -> Result<()> {
+    match (symbols.insert(name, symbol), symbols.get(name)) {
+        (None, _) | (Some(Symbol::Register), Some(Symbol::Register)) => Ok(()),
+        (Some(Symbol::Register), _) => bail!("{} is a register, on line {}", name, line_no),
+        (Some(_), _) => bail!("{} defined twice, again on line {}", name, line_no),
+    }
+}
+
+/// pc and the machine, which decide what a program does next. Only pc matters to a program
+/// without conditional instructions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Snapshot {
+    pc: usize,
+    state: Option<(BTreeMap<String, i64>, VecDeque<i64>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub pc: usize,
+    pub machine: Machine,
+    pub instructions: Vec<Instruction>,
+}
+
+/// Canonical text, one `op +n` per line, which parses back to the same instructions.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for instruction in &self.instructions {
+            writeln!(f, "{}", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+impl Program {
+    pub fn load_program(file_name: &str) -> Result<Program> {
+        Program::parse(&read_input(file_name)?)
+    }
+
+    /// Assembles `input` with the [`InstructionSet::standard`] instructions.
+    pub fn parse(input: &str) -> Result<Program> {
+        Program::parse_with(input, &STANDARD)
+    }
+
+    pub fn parse_with(input: &str, isa: &InstructionSet) -> Result<Program> {
+        Ok(Program {
+            pc: 0,
+            machine: Machine::default(),
+            instructions: assemble(input, isa)?,
+        })
+    }
+
+    pub fn accumulator(&self) -> i64 {
+        self.register(ACCUMULATOR)
+    }
+
+    pub fn register(&self, name: &str) -> i64 {
+        self.machine.registers.get(name).copied().unwrap_or(0)
+    }
+
+    /// Like the canonical text, but jumps within the program go to labels named after their target.
+    pub fn disassemble(&self) -> String {
+        let labelled = |pc: usize, instruction: &Instruction| match instruction.flow() {
+            Flow::Jump(offset) | Flow::Branch(offset) => self.target(pc, offset),
+            Flow::Next | Flow::Dynamic => None,
+        };
+        let targets: HashSet<usize> = self
+            .instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(pc, i)| labelled(pc, i))
+            .collect();
+
+        let mut result = String::new();
+        for (pc, instruction) in self.instructions.iter().enumerate() {
+            if targets.contains(&pc) {
+                result.push_str(&format!("l{}:\n", pc));
+            }
+            let mut line = instruction.op.name().to_owned();
+            for (idx, arg) in instruction.args.iter().enumerate() {
+                match labelled(pc, instruction) {
+                    Some(target) if instruction.op.jump_operand() == Some(idx) => {
+                        line.push_str(&format!(" l{}", target))
+                    }
+                    _ => line.push_str(&format!(" {}", arg)),
+                }
+            }
+            result.push_str(&format!("    {}\n", line));
+        }
+        if targets.contains(&self.instructions.len()) {
+            result.push_str(&format!("l{}:\n", self.instructions.len()));
+        }
+        result
+    }
+
+    /// Where an offset from `pc` lands, if that's inside the program or just past its end.
+    pub fn target(&self, pc: usize, offset: i64) -> Option<usize> {
+        let target = (pc as i64).checked_add(offset)?;
+        if target >= 0 && target <= self.instructions.len() as i64 {
+            Some(target as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Whether pc after some instruction depends on the machine.
+    pub fn branches(&self) -> bool {
+        self.instructions
+            .iter()
+            .any(|i| i.op.conditional() || i.flow() == Flow::Dynamic)
+    }
+
+    /// Everything that decides what happens from here on. Running into the same snapshot twice
+    /// means the program loops forever.
+    pub fn snapshot(&self, branches: bool) -> Snapshot {
+        Snapshot {
+            pc: self.pc,
+            state: if branches {
+                Some((self.machine.registers.clone(), self.machine.input.clone()))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Runs the instruction at pc. Returns why not if the program can't go on: it already ended,
+    /// the instruction would jump anywhere but into the program or just past its end, or it waits
+    /// for input.
+    pub fn step(&mut self) -> Option<ExecutionOutcome> {
+        let pc = self.pc;
+        let instruction = match self.instructions.get(pc) {
+            Some(instruction) => instruction,
+            None if pc == self.instructions.len() => return Some(ExecutionOutcome::Terminated),
+            None => {
+                return Some(ExecutionOutcome::JumpOutOfRange {
+                    pc,
+                    target: pc as i64,
+                })
+            }
+        };
+
+        match instruction.op.execute(&instruction.args, &mut self.machine) {
+            Effect::Next => self.pc += 1,
+            Effect::Jump(offset) => match self.target(pc, offset) {
+                Some(target) => self.pc = target,
+                None => {
+                    return Some(ExecutionOutcome::JumpOutOfRange {
+                        pc,
+                        target: (pc as i64).saturating_add(offset),
+                    })
+                }
+            },
+            Effect::Wait => return Some(ExecutionOutcome::WaitingForInput { pc }),
+        }
+        None
+    }
+
+    /// Runs until the program ends, waits for input or is about to repeat itself.
+    pub fn run_till_end(&mut self) -> ExecutionOutcome {
+        self.run_limited(usize::MAX)
+    }
+
+    /// Like [`Program::run_till_end`], but gives up after `max_steps` instructions.
+    pub fn run_limited(&mut self, max_steps: usize) -> ExecutionOutcome {
+        let branches = self.branches();
+        let mut seen = HashSet::new();
+        let mut steps = 0;
+        loop {
+            if !seen.insert(self.snapshot(branches)) {
+                return ExecutionOutcome::LoopDetected { pc: self.pc, steps };
+            }
+            if steps == max_steps {
+                return ExecutionOutcome::StepLimitExceeded;
+            }
+            if let Some(outcome) = self.step() {
+                return outcome;
+            }
+            steps += 1;
+        }
+    }
+}
+
+/// How a run of a [`Program`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionOutcome {
+    /// pc is exactly one past the last instruction.
+    Terminated,
+    /// The instruction at `pc` was about to run again in the same state, after `steps` instructions.
+    LoopDetected {
+        pc: usize,
+        steps: usize,
+    },
+    /// The jump at `pc` would go to `target`, outside the program. Targets past the range of an
+    /// `i64` saturate.
+    JumpOutOfRange {
+        pc: usize,
+        target: i64,
+    },
+    /// The instruction at `pc` needs input. Running again after adding some carries on from there.
+    WaitingForInput {
+        pc: usize,
+    },
+    StepLimitExceeded,
+}
+
+impl fmt::Display for ExecutionOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionOutcome::Terminated => write!(f, "terminated"),
+            ExecutionOutcome::LoopDetected { pc, steps } => {
+                write!(f, "loop detected at pc {} after {} steps", pc, steps)
+            }
+            ExecutionOutcome::JumpOutOfRange { pc, target } => {
+                write!(f, "jump from pc {} to {} leaves the program", pc, target)
+            }
+            ExecutionOutcome::WaitingForInput { pc } => write!(f, "waiting for input at pc {}", pc),
+            ExecutionOutcome::StepLimitExceeded => write!(f, "step limit exceeded"),
+        }
+    }
+}
+
+/// Instructions which fix a program when swapped between `nop` and `jmp`, and the accumulator it
+/// then ends with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair {
+    pub flips: Vec<usize>,
+    pub accumulator: i64,
+}
+
+impl Program {
+    /// Finds the one `nop`/`jmp` swap which lets the program terminate, carrying on from the
+    /// current pc. A program which terminates already needs no swaps.
+    ///
+    /// Works backwards from the end to find every pc which terminates, then runs the program once,
+    /// stopping at the first instruction whose swapped version goes to one of those.
+    pub fn repair(&self) -> Result<Repair> {
+        self.check_repairable()?;
+        let len = self.instructions.len();
+
+        let mut sources = vec![vec![]; len + 1];
+        for pc in 0..len {
+            if let Some(next) = self.successor(pc, &self.instructions[pc]) {
+                sources[next].push(pc);
+            }
+        }
+        let mut terminates = vec![false; len + 1];
+        let mut todo = vec![len];
+        while let Some(pc) = todo.pop() {
+            if !terminates[pc] {
+                terminates[pc] = true;
+                todo.extend(&sources[pc]);
+            }
+        }
+
+        if terminates.get(self.pc) == Some(&true) {
+            let mut finished = self.clone();
+            finished.run_till_end();
+            return Ok(Repair {
+                flips: vec![],
+                accumulator: finished.accumulator(),
+            });
+        }
+
+        let mut visited = vec![false; len];
+        let mut pc = self.pc;
+        while pc < len && !visited[pc] {
+            visited[pc] = true;
+            if let Some(flipped) = flip(&self.instructions[pc]) {
+                if let Some(next) = self.successor(pc, &flipped) {
+                    if terminates[next] {
+                        let mut fixed = self.clone();
+                        fixed.instructions[pc] = flipped;
+                        fixed.run_till_end();
+                        return Ok(Repair {
+                            flips: vec![pc],
+                            accumulator: fixed.accumulator(),
+                        });
+                    }
+                }
+            }
+            pc = match self.successor(pc, &self.instructions[pc]) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        bail!("No single swap makes the program terminate")
+    }
+
+    /// Every set of at most `max_flips` swaps which lets the program terminate, fewest first.
+    /// Only instructions which actually run are swapped, so no set contains a pointless one.
+    pub fn repairs(&self, max_flips: usize) -> Result<Vec<Repair>> {
+        self.check_repairable()?;
+        let mut result = vec![];
+        self.search_repairs(
+            self.pc,
+            self.machine.clone(),
+            &mut vec![false; self.instructions.len()],
+            &mut vec![],
+            max_flips,
+            &mut result,
+        );
+        result.sort_by(|a, b| (a.flips.len(), &a.flips).cmp(&(b.flips.len(), &b.flips)));
+        Ok(result)
+    }
+
+    fn search_repairs(
+        &self,
+        mut pc: usize,
+        mut machine: Machine,
+        visited: &mut Vec<bool>,
+        flips: &mut Vec<usize>,
+        max_flips: usize,
+        result: &mut Vec<Repair>,
+    ) {
+        let mut marked = vec![];
+        loop {
+            if pc == self.instructions.len() {
+                let acc = Operand::Register(ACCUMULATOR.to_owned());
+                result.push(Repair {
+                    flips: flips.clone(),
+                    accumulator: machine.get(&acc),
+                });
+                break;
+            }
+            if pc > self.instructions.len() || visited[pc] {
+                break;
+            }
+            visited[pc] = true;
+            marked.push(pc);
+
+            let instruction = &self.instructions[pc];
+            if flips.len() < max_flips {
+                if let Some(flipped) = flip(instruction) {
+                    if let Some(next) = self.successor(pc, &flipped) {
+                        let mut machine = machine.clone();
+                        flipped.op.execute(&flipped.args, &mut machine);
+                        flips.push(pc);
+                        self.search_repairs(next, machine, visited, flips, max_flips, result);
+                        flips.pop();
+                    }
+                }
+            }
+
+            instruction.op.execute(&instruction.args, &mut machine);
+            pc = match self.successor(pc, instruction) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        for pc in marked {
+            visited[pc] = false;
+        }
+    }
+
+    /// Without conditional instructions every instruction has exactly one place to go next.
+    fn check_repairable(&self) -> Result<()> {
+        if self.branches() {
+            bail!("Can only repair programs without conditional instructions");
+        }
+        Ok(())
+    }
+
+    /// Where `instruction` at `pc` sends a program without conditional instructions, if anywhere.
+    fn successor(&self, pc: usize, instruction: &Instruction) -> Option<usize> {
+        match instruction.flow() {
+            Flow::Jump(offset) => self.target(pc, offset),
+            _ => Some(pc + 1),
+        }
+    }
+}
+
+/// `instruction` with `nop` and `jmp` swapped.
+fn flip(instruction: &Instruction) -> Option<Instruction> {
+    let name = match instruction.op.name() {
+        "nop" => "jmp",
+        "jmp" => "nop",
+        _ => return None,
+    };
+    Some(Instruction {
+        op: InstructionSet::builtin(name)?,
+        args: instruction.args.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let program = Program::load_program("day8_smoke.txt")?;
+        assert_eq!(read_input("day8_smoke.txt")?, program.to_string());
+        assert_eq!(program, Program::parse(&program.to_string())?);
+        assert_eq!(program, Program::parse(&program.disassemble())?);
+
+        let program = Program::load_program("day8.txt")?;
+        assert_eq!(program, Program::parse(&program.disassemble())?);
+        Ok(())
+    }
+
+    #[test]
+    fn assemble_labels() -> Result<()> {
+        let program = Program::parse(
+            "; counts down from three
+            .const START 3
+            .const DOWN -1
+                acc START
+            loop: acc DOWN   # four times round
+                jmp loop
+            end: nop end
+            ",
+        )?;
+        assert_eq!("acc +3\nacc -1\njmp -1\nnop +0\n", program.to_string());
+        assert_eq!(
+            "    acc +3\nl1:\n    acc -1\n    jmp l1\n    nop +0\n",
+            program.disassemble()
+        );
+
+        // Labels may come before they're defined, or after the last instruction.
+        let program = Program::parse("jmp done\nacc +1\ndone:\n")?;
+        assert_eq!("jmp +2\nacc +1\n", program.to_string());
+
+        assert!(Program::parse("jmp nowhere").is_err());
+        assert!(Program::parse("sub +1").is_err());
+        assert!(Program::parse("x: nop +0\nx: nop +0").is_err());
+        assert!(Program::parse(".const x 1\nx: nop +0").is_err());
+        assert!(Program::parse(".const x y").is_err());
+        assert!(Program::parse("acc 1 2").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn outcomes() -> Result<()> {
+        let mut program = Program::load_program("day8_smoke.txt")?;
+        assert_eq!(
+            ExecutionOutcome::LoopDetected { pc: 1, steps: 7 },
+            program.run_till_end()
+        );
+        assert_eq!(5, program.accumulator());
+
+        let mut program = Program::parse("acc +1\njmp +2\nacc +5\n")?;
+        assert_eq!(ExecutionOutcome::Terminated, program.run_till_end());
+        assert_eq!(3, program.pc);
+        assert_eq!(Some(ExecutionOutcome::Terminated), program.step());
+
+        // Used to wrap around to a huge pc and pass for the end of the program.
+        let mut program = Program::parse("nop +0\njmp -2\n")?;
+        assert_eq!(
+            ExecutionOutcome::JumpOutOfRange { pc: 1, target: -1 },
+            program.run_till_end()
+        );
+        assert_eq!(1, program.pc);
+
+        let mut program = Program::parse("jmp +3\nnop +0\n")?;
+        assert_eq!(
+            ExecutionOutcome::JumpOutOfRange { pc: 0, target: 3 },
+            program.run_till_end()
+        );
+
+        let mut program = Program::parse("nop +0\njmp +9223372036854775807\n")?;
+        assert_eq!(
+            ExecutionOutcome::JumpOutOfRange {
+                pc: 1,
+                target: i64::MAX
+            },
+            program.run_till_end()
+        );
+
+        let mut program = Program::parse("acc +1\nacc +1\nacc +1\n")?;
+        assert_eq!(ExecutionOutcome::StepLimitExceeded, program.run_limited(2));
+        assert_eq!(2, program.accumulator());
+        Ok(())
+    }
+
+    #[test]
+    fn registers() -> Result<()> {
+        // Multiplies 5 by 3 the long way round.
+        let mut program = Program::parse(
+            "    set a 5
+            loop: add b 3
+                add a -1
+                jnz a loop
+                out b
+                mul b b
+                jgz b end
+                acc -100
+            end:",
+        )?;
+        assert_eq!(ExecutionOutcome::Terminated, program.run_till_end());
+        assert_eq!(0, program.register("a"));
+        assert_eq!(225, program.register("b"));
+        assert_eq!(0, program.accumulator());
+        assert_eq!(vec![15], Vec::from(program.machine.output.clone()));
+        assert_eq!(
+            "set a +5\nadd b +3\nadd a -1\njnz a -2\nout b\nmul b b\njgz b +2\nacc -100\n",
+            program.to_string()
+        );
+        assert_eq!(
+            program.instructions,
+            Program::parse(&program.disassemble())?.instructions
+        );
+
+        // The same pc comes round again with different registers, which isn't a loop...
+        let mut program = Program::parse(".reg n\nin n\nadd n -1\njgz n -1\n")?;
+        program.machine.input.push_back(3);
+        assert_eq!(ExecutionOutcome::Terminated, program.run_till_end());
+        // ...but the same registers as well is.
+        let mut program = Program::parse("set n 1\njnz n +0\n")?;
+        assert_eq!(
+            ExecutionOutcome::LoopDetected { pc: 1, steps: 2 },
+            program.run_till_end()
+        );
+
+        assert!(Program::parse("set 5 5").is_err());
+        assert!(Program::parse("set a").is_err());
+        assert!(Program::parse("jnz a +1").is_err());
+        assert!(Program::parse("a: set a 1").is_err());
+        assert!(Program::parse("set acc 1\n.reg acc").is_ok());
+        assert!(Program::parse_with("set a 1", &InstructionSet::day8()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn input_output() -> Result<()> {
+        let mut program = Program::parse("loop: in x\nmul x 2\nout x\njmp loop\n")?;
+        assert_eq!(
+            ExecutionOutcome::WaitingForInput { pc: 0 },
+            program.run_till_end()
+        );
+        program.machine.input.extend(&[1, 2, 3]);
+        assert_eq!(
+            ExecutionOutcome::WaitingForInput { pc: 0 },
+            program.run_till_end()
+        );
+        assert_eq!(vec![2, 4, 6], Vec::from(program.machine.output.clone()));
+        Ok(())
+    }
+
+    /// `dec x` subtracts one from a register.
+    struct Dec;
+
+    impl OpCode for Dec {
+        fn name(&self) -> &'static str {
+            "dec"
+        }
+
+        fn operands(&self) -> &'static [OperandKind] {
+            &REGISTER
+        }
+
+        fn execute(&self, args: &[Operand], machine: &mut Machine) -> Effect {
+            machine.set(&args[0], machine.get(&args[0]) - 1);
+            Effect::Next
+        }
+    }
+
+    #[test]
+    fn custom_instruction() -> Result<()> {
+        let mut isa = InstructionSet::standard();
+        isa.register(Dec);
+        let mut program = Program::parse_with("set a 3\nloop: dec a\njgz a loop\n", &isa)?;
+        assert_eq!(ExecutionOutcome::Terminated, program.run_till_end());
+        assert_eq!(0, program.register("a"));
+        assert_eq!("dec a", program.instructions[1].to_string());
+        assert!(Program::parse("dec a").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn repair() -> Result<()> {
+        let program = Program::load_program("day8_smoke.txt")?;
+        let expected = Repair {
+            flips: vec![7],
+            accumulator: 8,
+        };
+        assert_eq!(expected, program.repair()?);
+        assert_eq!(vec![expected], program.repairs(1)?);
+        let program = Program::load_program("day8.txt")?;
+        assert_eq!(vec![program.repair()?], program.repairs(1)?);
+
+        // Either loop alone still traps the program.
+        let program = Program::parse("acc +1\njmp +0\nacc +2\njmp +0\n")?;
+        assert!(program.repair().is_err());
+        assert!(program.repairs(1)?.is_empty());
+        assert_eq!(
+            vec![Repair {
+                flips: vec![1, 3],
+                accumulator: 3
+            }],
+            program.repairs(2)?
+        );
+
+        let program = Program::parse("acc +1\njmp +2\nacc +5\n")?;
+        let nothing = Repair {
+            flips: vec![],
+            accumulator: 1,
+        };
+        assert_eq!(nothing, program.repair()?);
+        assert_eq!(vec![nothing], program.repairs(0)?);
+
+        // Starting past the only swap which would have helped.
+        let mut program = Program::parse("nop +3\njmp +0\njmp -1\n")?;
+        assert_eq!(vec![0], program.repair()?.flips);
+        program.step();
+        assert!(program.repair().is_err());
+        assert!(program.repairs(1)?.is_empty());
+
+        assert!(Program::parse("jnz acc +0")?.repair().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, BufRead, BufReader, Cursor, Lines, Read},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{bail, Context, Result};
+use lazy_static::lazy_static;
+
+/// Environment variable which, when set, is searched before any of the default locations.
+pub const INPUT_DIR_VAR: &str = "AOC_INPUT_DIR";
+
+/// Input name which reads from standard input rather than a file.
+pub const STDIN: &str = "-";
+
+const RES_DIR: &str = "res";
+const CACHE_DIR: &str = "aoc_2020";
+
+/// Finds puzzle inputs by searching an ordered list of root directories.
+#[derive(Debug, Clone)]
+pub struct InputResolver {
+    roots: Vec<PathBuf>,
+}
+
+impl Default for InputResolver {
+    fn default() -> Self {
+        InputResolver::new()
+    }
+}
+
+impl InputResolver {
+    /// Searches `$AOC_INPUT_DIR` (if set), `./res`, `$CARGO_MANIFEST_DIR/res` and then the user cache dir.
+    pub fn new() -> InputResolver {
+        let mut roots = vec![];
+        if let Some(dir) = env::var_os(INPUT_DIR_VAR) {
+            roots.push(PathBuf::from(dir));
+        }
+        roots.push(PathBuf::from(RES_DIR));
+        roots.push(Path::new(env!("CARGO_MANIFEST_DIR")).join(RES_DIR));
+        if let Some(cache) = user_cache_dir() {
+            roots.push(cache.join(CACHE_DIR));
+        }
+        InputResolver { roots }
+    }
+
+    /// Searches `root` before falling back to the default locations.
+    pub fn with_root<P: AsRef<Path>>(root: P) -> InputResolver {
+        let mut resolver = InputResolver::new();
+        resolver.roots.insert(0, root.as_ref().to_owned());
+        resolver
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// A `file_name` with a directory in it is tried as given first. A bare name is looked for
+    /// under each root, and only then in the current directory.
+    pub fn resolve(&self, file_name: &str) -> Result<PathBuf> {
+        let direct = Path::new(file_name);
+        let explicit = direct.is_absolute() || direct.components().count() > 1;
+        let mut candidates = vec![];
+        if explicit {
+            candidates.push(direct.to_owned());
+        }
+        candidates.extend(self.roots.iter().map(|root| root.join(file_name)));
+        if !explicit {
+            candidates.push(direct.to_owned());
+        }
+
+        let mut tried = vec![];
+        for candidate in candidates {
+            if candidate.is_file() {
+                return Ok(candidate);
+            }
+            // An absolute `file_name` ignores the root, so it comes out the same every time.
+            if !tried.contains(&candidate) {
+                tried.push(candidate);
+            }
+        }
+
+        let tried: Vec<String> = tried.iter().map(|p| format!("  {}", p.display())).collect();
+        bail!(
+            "Could not find input {}. Tried:\n{}",
+            file_name,
+            tried.join("\n")
+        );
+    }
+
+    pub fn open(&self, file_name: &str) -> Result<File> {
+        let path = self.resolve(file_name)?;
+        File::open(&path).with_context(|| format!("Could not open {}", path.display()))
+    }
+
+    pub fn read_file(&self, file_name: &str) -> Result<Lines<BufReader<File>>> {
+        Ok(BufReader::new(self.open(file_name)?).lines())
+    }
+
+    pub fn read_to_string(&self, file_name: &str) -> Result<String> {
+        read_all(self.open(file_name)?).with_context(|| format!("Could not read {}", file_name))
+    }
+}
+
+/// Reads everything from `reader`, e.g. a socket or an in-memory buffer, as UTF-8 text.
+pub fn read_all<R: Read>(mut reader: R) -> Result<String> {
+    let mut result = String::new();
+    reader
+        .read_to_string(&mut result)
+        .context("Could not read input")?;
+    Ok(result)
+}
+
+/// Standard input is read once and replayed for every later request, so both parts can share it.
+pub fn read_stdin() -> Result<Cursor<String>> {
+    lazy_static! {
+        static ref BUFFER: Mutex<Option<String>> = Mutex::new(None);
+    }
+    let mut buffer = BUFFER.lock().unwrap();
+    if buffer.is_none() {
+        let mut contents = String::new();
+        io::stdin()
+            .read_to_string(&mut contents)
+            .context("Could not read stdin")?;
+        *buffer = Some(contents);
+    }
+    Ok(Cursor::new(buffer.as_ref().unwrap().clone()))
+}
+
+fn user_cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CACHE_HOME") {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = env::var_os("LOCALAPPDATA") {
+        return Some(PathBuf::from(dir));
+    }
+    env::var_os("HOME").map(|home| Path::new(&home).join(".cache"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_from_manifest() -> Result<()> {
+        let path = InputResolver::new().resolve("day1.txt")?;
+        assert!(path.ends_with(Path::new(RES_DIR).join("day1.txt")));
+        Ok(())
+    }
+
+    #[test]
+    fn explicit_root_first() -> Result<()> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let path = InputResolver::with_root(&root).resolve("lib.rs")?;
+        assert_eq!(root.join("lib.rs"), path);
+        Ok(())
+    }
+
+    #[test]
+    fn direct_path() -> Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let resolved = InputResolver::new().resolve(path.to_str().unwrap())?;
+        assert_eq!(path, resolved);
+        Ok(())
+    }
+
+    #[test]
+    fn current_directory() -> Result<()> {
+        // Tests run from the manifest directory, which has no res/Cargo.toml to find instead.
+        let resolved = InputResolver::new().resolve("Cargo.toml")?;
+        assert_eq!(Path::new("Cargo.toml"), resolved);
+        Ok(())
+    }
+
+    #[test]
+    fn root_before_current_directory() -> Result<()> {
+        let root = env::temp_dir().join(format!("aoc_2020_input_{}", std::process::id()));
+        std::fs::create_dir_all(&root)?;
+        std::fs::write(root.join("Cargo.toml"), "")?;
+        let resolved = InputResolver::with_root(&root).resolve("Cargo.toml");
+        std::fs::remove_dir_all(&root)?;
+        assert_eq!(root.join("Cargo.toml"), resolved?);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_lists_tried() {
+        let resolver = InputResolver::with_root("nowhere");
+        let message = resolver.resolve("no_such_day.txt").unwrap_err().to_string();
+        for root in resolver.roots() {
+            assert!(message.contains(&root.join("no_such_day.txt").display().to_string()));
+        }
+
+        let message = resolver
+            .resolve("/no_such_day.txt")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            "Could not find input /no_such_day.txt. Tried:\n  /no_such_day.txt",
+            message
+        );
+    }
+}
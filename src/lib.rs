@@ -1,5 +1,9 @@
 #![allow(dead_code)]
 
+pub mod answers;
+pub mod bench;
+pub mod cfg;
+pub mod crt;
 mod day1;
 mod day10;
 mod day11;
@@ -25,28 +29,65 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
+pub mod debugger;
+pub mod grid;
+pub mod handheld;
+pub mod input;
+pub mod report;
+pub mod solver;
 
-use std::{
-    fs::File,
-    io::{BufRead, BufReader, Lines},
-};
+use std::io::{BufRead, Lines};
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 
-const FILE_BASE: &str = r"res\";
-
-pub fn read_file(file_name: &str) -> Result<Lines<BufReader<File>>> {
-    let input = File::open(FILE_BASE.to_owned() + file_name).context("Could not open file")?;
-    let reader = BufReader::new(input);
+pub use day17::{NWorld, Rule};
+pub use input::{InputResolver, STDIN};
+pub use report::PartResult;
+pub use solver::{Puzzle, Solver};
 
+/// Reads `file_name` via the default [`InputResolver`], or standard input when given [`STDIN`].
+pub fn read_file(file_name: &str) -> Result<Lines<Box<dyn BufRead>>> {
+    let reader: Box<dyn BufRead> = if file_name == STDIN {
+        Box::new(input::read_stdin()?)
+    } else {
+        Box::new(std::io::BufReader::new(
+            InputResolver::new().open(file_name)?,
+        ))
+    };
     Ok(reader.lines())
 }
 
+/// Reads the whole of `file_name` (or standard input) into memory.
+pub fn read_input(file_name: &str) -> Result<String> {
+    if file_name == STDIN {
+        input::read_all(input::read_stdin()?)
+    } else {
+        InputResolver::new().read_to_string(file_name)
+    }
+}
+
+/// Solves one part of one day against `file_name`, returning the answer as text.
+pub fn solve(day: u32, part: u32, file_name: &str) -> Result<String> {
+    solve_str(day, part, &read_input(file_name)?)
+}
+
+/// Solves one part of one day against puzzle text that is already in memory.
+pub fn solve_str(day: u32, part: u32, input: &str) -> Result<String> {
+    solver::lookup(day)?.solve(part, input)
+}
+
+pub fn solve_reader<R: BufRead>(day: u32, part: u32, reader: R) -> Result<String> {
+    solve_str(day, part, &input::read_all(reader)?)
+}
+
 fn load_numbers(file_name: &str) -> Result<Vec<i64>> {
+    parse_numbers(&read_input(file_name)?)
+}
+
+fn parse_numbers(input: &str) -> Result<Vec<i64>> {
     let mut result = vec![];
 
-    for line in read_file(file_name)? {
-        let line = line?;
+    for line in input.lines() {
         let line = line.trim();
         result.push(line.parse()?);
     }
@@ -55,8 +96,27 @@ fn load_numbers(file_name: &str) -> Result<Vec<i64>> {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    const DAY1_SMOKE: &str = "1721\n979\n366\n299\n675\n1456\n";
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn solve_from_str() -> Result<()> {
+        assert_eq!("514579", solve_str(1, 1, DAY1_SMOKE)?);
+        assert_eq!("241861950", solve_str(1, 2, DAY1_SMOKE)?);
+        Ok(())
+    }
+
+    #[test]
+    fn solve_from_reader() -> Result<()> {
+        assert_eq!("514579", solve_reader(1, 1, DAY1_SMOKE.as_bytes())?);
+        let day1 = day1::Day1::from_reader(std::io::Cursor::new(DAY1_SMOKE))?;
+        assert_eq!(241861950, day1.part2()?);
+        Ok(())
+    }
 }
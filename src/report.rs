@@ -0,0 +1,154 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde::{Serialize, Serializer};
+
+use crate::{solver, InputResolver};
+
+/// Outcome of solving one part of one day, written out as a single JSON line.
+#[derive(Debug, Clone, Serialize)]
+pub struct PartResult {
+    pub day: u32,
+    pub part: u32,
+    pub answer: Option<String>,
+    pub error: Option<String>,
+    #[serde(rename = "parse_time_us", serialize_with = "micros")]
+    pub parse_time: Duration,
+    #[serde(rename = "solve_time_us", serialize_with = "micros")]
+    pub solve_time: Duration,
+    pub input_hash: Option<String>,
+}
+
+impl PartResult {
+    pub(crate) fn failed(day: u32, part: u32, error: &anyhow::Error) -> PartResult {
+        PartResult {
+            day,
+            part,
+            answer: None,
+            error: Some(format!("{:#}", error)),
+            parse_time: Duration::default(),
+            solve_time: Duration::default(),
+            input_hash: None,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+fn micros<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_micros() as u64)
+}
+
+/// 64-bit FNV-1a, hex encoded. Stable across platforms and compiler versions, unlike `DefaultHasher`.
+pub fn input_hash(input: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in input.bytes() {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Parses `input` once and then solves each of `parts`. Failures are recorded rather than returned.
+pub fn run(day: u32, parts: &[u32], input: &str) -> Vec<PartResult> {
+    let entry = match solver::lookup(day) {
+        Ok(entry) => entry,
+        Err(e) => {
+            return parts
+                .iter()
+                .map(|p| PartResult::failed(day, *p, &e))
+                .collect()
+        }
+    };
+    let hash = input_hash(input);
+
+    let start = Instant::now();
+    let parsed = (entry.parse)(input);
+    let parse_time = start.elapsed();
+
+    let mut result = vec![];
+    for part in parts {
+        let mut part_result = PartResult {
+            day,
+            part: *part,
+            answer: None,
+            error: None,
+            parse_time,
+            solve_time: Duration::default(),
+            input_hash: Some(hash.clone()),
+        };
+        match &parsed {
+            Ok(puzzle) => {
+                let start = Instant::now();
+                let answer = puzzle.solve(*part);
+                part_result.solve_time = start.elapsed();
+                match answer {
+                    Ok(answer) => part_result.answer = Some(answer),
+                    Err(e) => part_result.error = Some(format!("{:#}", e)),
+                }
+            }
+            Err(e) => part_result.error = Some(format!("{:#}", e)),
+        }
+        result.push(part_result);
+    }
+    result
+}
+
+/// Runs both parts of every registered day against `day{N}.txt` as found by `resolver`.
+pub fn run_all(resolver: &InputResolver) -> Vec<PartResult> {
+    let mut result = vec![];
+    for entry in solver::registry() {
+        match resolver.read_to_string(&format!("day{}.txt", entry.day)) {
+            Ok(input) => result.extend(run(entry.day, &[1, 2], &input)),
+            Err(e) => {
+                result.push(PartResult::failed(entry.day, 1, &e));
+                result.push(PartResult::failed(entry.day, 2, &e));
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY1_SMOKE: &str = "1721\n979\n366\n299\n675\n1456\n";
+
+    #[test]
+    fn hash_is_stable() {
+        assert_eq!("cbf29ce484222325", input_hash(""));
+        assert_eq!("af63dc4c8601ec8c", input_hash("a"));
+    }
+
+    #[test]
+    fn run_smoke() -> Result<()> {
+        let results = run(1, &[1, 2], DAY1_SMOKE);
+        assert_eq!(2, results.len());
+        assert_eq!(Some("514579".to_owned()), results[0].answer);
+        assert_eq!(Some("241861950".to_owned()), results[1].answer);
+        assert_eq!(results[0].input_hash, results[1].input_hash);
+
+        let json: serde_json::Value = serde_json::from_str(&results[0].to_json()?)?;
+        assert_eq!(1, json["day"]);
+        assert_eq!(1, json["part"]);
+        assert_eq!("514579", json["answer"]);
+        assert!(json["error"].is_null());
+        assert!(json["parse_time_us"].is_u64());
+        assert!(json["solve_time_us"].is_u64());
+        Ok(())
+    }
+
+    #[test]
+    fn run_records_errors() {
+        let results = run(1, &[3], DAY1_SMOKE);
+        assert_eq!(None, results[0].answer);
+        assert_eq!(Some("No part 3".to_owned()), results[0].error);
+
+        let results = run(26, &[1], DAY1_SMOKE);
+        assert_eq!(Some("No solver for day 26".to_owned()), results[0].error);
+        assert_eq!(None, results[0].input_hash);
+    }
+}
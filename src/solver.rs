@@ -0,0 +1,170 @@
+use std::{fmt::Display, io::BufRead};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    day1, day10, day11, day12, day13, day14, day15, day16, day17, day18, day19, day2, day20, day21,
+    day22, day23, day24, day25, day3, day4, day5, day6, day7, day8, day9, input,
+};
+
+/// A single day's puzzle: parse the input once, then answer either part from it.
+pub trait Solver: Sized {
+    type Part1: Display;
+    type Part2: Display;
+
+    fn parse(input: &str) -> Result<Self>;
+    fn part1(&self) -> Result<Self::Part1>;
+    fn part2(&self) -> Result<Self::Part2>;
+
+    fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        Self::parse(&input::read_all(reader)?)
+    }
+
+    fn load(file_name: &str) -> Result<Self> {
+        Self::parse(&crate::read_input(file_name)?)
+    }
+}
+
+/// Object-safe view of a parsed [`Solver`] so that every day fits in the same registry.
+pub trait Puzzle {
+    fn solve(&self, part: u32) -> Result<String>;
+}
+
+impl<S: Solver> Puzzle for S {
+    fn solve(&self, part: u32) -> Result<String> {
+        match part {
+            1 => Ok(self.part1()?.to_string()),
+            2 => Ok(self.part2()?.to_string()),
+            _ => bail!("No part {}", part),
+        }
+    }
+}
+
+pub struct Entry {
+    pub day: u32,
+    pub parse: fn(&str) -> Result<Box<dyn Puzzle>>,
+}
+
+impl Entry {
+    pub fn solve(&self, part: u32, input: &str) -> Result<String> {
+        (self.parse)(input)?.solve(part)
+    }
+}
+
+fn parse_boxed<S: Solver + 'static>(input: &str) -> Result<Box<dyn Puzzle>> {
+    Ok(Box::new(S::parse(input)?))
+}
+
+static REGISTRY: [Entry; 25] = [
+    Entry {
+        day: 1,
+        parse: parse_boxed::<day1::Day1>,
+    },
+    Entry {
+        day: 2,
+        parse: parse_boxed::<day2::Day2>,
+    },
+    Entry {
+        day: 3,
+        parse: parse_boxed::<day3::Day3>,
+    },
+    Entry {
+        day: 4,
+        parse: parse_boxed::<day4::Day4>,
+    },
+    Entry {
+        day: 5,
+        parse: parse_boxed::<day5::Day5>,
+    },
+    Entry {
+        day: 6,
+        parse: parse_boxed::<day6::Day6>,
+    },
+    Entry {
+        day: 7,
+        parse: parse_boxed::<day7::Day7>,
+    },
+    Entry {
+        day: 8,
+        parse: parse_boxed::<day8::Day8>,
+    },
+    Entry {
+        day: 9,
+        parse: parse_boxed::<day9::Day9>,
+    },
+    Entry {
+        day: 10,
+        parse: parse_boxed::<day10::Day10>,
+    },
+    Entry {
+        day: 11,
+        parse: parse_boxed::<day11::Day11>,
+    },
+    Entry {
+        day: 12,
+        parse: parse_boxed::<day12::Day12>,
+    },
+    Entry {
+        day: 13,
+        parse: parse_boxed::<day13::Day13>,
+    },
+    Entry {
+        day: 14,
+        parse: parse_boxed::<day14::Day14>,
+    },
+    Entry {
+        day: 15,
+        parse: parse_boxed::<day15::Day15>,
+    },
+    Entry {
+        day: 16,
+        parse: parse_boxed::<day16::Day16>,
+    },
+    Entry {
+        day: 17,
+        parse: parse_boxed::<day17::Day17>,
+    },
+    Entry {
+        day: 18,
+        parse: parse_boxed::<day18::Day18>,
+    },
+    Entry {
+        day: 19,
+        parse: parse_boxed::<day19::Day19>,
+    },
+    Entry {
+        day: 20,
+        parse: parse_boxed::<day20::Day20>,
+    },
+    Entry {
+        day: 21,
+        parse: parse_boxed::<day21::Day21>,
+    },
+    Entry {
+        day: 22,
+        parse: parse_boxed::<day22::Day22>,
+    },
+    Entry {
+        day: 23,
+        parse: parse_boxed::<day23::Day23>,
+    },
+    Entry {
+        day: 24,
+        parse: parse_boxed::<day24::Day24>,
+    },
+    Entry {
+        day: 25,
+        parse: parse_boxed::<day25::Day25>,
+    },
+];
+
+pub fn registry() -> &'static [Entry] {
+    &REGISTRY
+}
+
+pub fn lookup(day: u32) -> Result<&'static Entry> {
+    registry()
+        .iter()
+        .find(|e| e.day == day)
+        .with_context(|| format!("No solver for day {}", day))
+}